@@ -0,0 +1,83 @@
+use crate::{
+  ir::register::{Register, REGISTER_COUNT},
+  report::message::Message,
+};
+
+#[derive(Debug, Clone)]
+pub struct RegisterAllocator {
+  temporaries: [bool; REGISTER_COUNT as usize],
+  saved: u8,
+}
+
+impl RegisterAllocator {
+  pub fn new() -> Self {
+    Self {
+      temporaries: [false; REGISTER_COUNT as usize],
+      saved: 0,
+    }
+  }
+
+  /// Allocates a T register, to be released with `free` once consumed
+  pub fn temporary(&mut self) -> Register {
+    let index = self
+      .temporaries
+      .iter()
+      .position(|used| !used)
+      .unwrap_or_else(|| {
+        // Operands are spilled before registers run out
+        Message::compiler_bug("Ran out of temporary registers")
+          .report_and_exit(1)
+      });
+    self.temporaries[index] = true;
+    Register::T(index as u8)
+  }
+
+  /// Allocates an S register for the rest of the function, if any is left
+  pub fn saved(&mut self) -> Option<Register> {
+    if self.saved < REGISTER_COUNT {
+      self.saved += 1;
+      Some(Register::S(self.saved - 1))
+    } else {
+      None
+    }
+  }
+
+  /// Marks a T register freed earlier as holding a value again
+  pub fn take(&mut self, reg: Register) {
+    match reg {
+      Register::T(index) => self.temporaries[index as usize] = true,
+      Register::S(_) => {
+        Message::compiler_bug(&format!("Tried to take saved register {reg}"))
+          .report_and_exit(1)
+      }
+    }
+  }
+
+  /// T registers currently holding a value
+  pub fn live(&self) -> Vec<Register> {
+    (0..REGISTER_COUNT)
+      .filter(|index| self.temporaries[*index as usize])
+      .map(Register::T)
+      .collect()
+  }
+
+  /// Number of T registers not holding a value
+  pub fn available(&self) -> usize {
+    self.temporaries.iter().filter(|used| !**used).count()
+  }
+
+  /// S registers handed out so far
+  pub fn used_saved(&self) -> Vec<Register> {
+    (0..self.saved).map(Register::S).collect()
+  }
+
+  pub fn free(&mut self, reg: Register) {
+    match reg {
+      Register::T(index) => self.temporaries[index as usize] = false,
+      Register::S(_) => {
+        Message::compiler_bug(&format!("Tried to free saved register {reg}"))
+          .report_and_exit(1)
+      }
+    }
+  }
+}
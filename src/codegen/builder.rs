@@ -0,0 +1,2278 @@
+use super::{
+  allocator::RegisterAllocator,
+  frame::{is_scalar, parameter_passing, CallStack, Passing},
+  function::McFunction,
+  helper::Helper,
+  CodeGenerator,
+};
+use crate::{
+  grammar::{
+    builtins::{parse_format, BuiltinFn, BuiltinType, FormatPart},
+    identifier::{CallTarget, FullIdentifier, Name, Type},
+    operators::{AssignOp, BinOp, UnOp},
+    parser::ast::Expression,
+    semifier::{
+      ast::{
+        always_jumps, may_jump, may_return, FnDecl, FnStatement,
+        ResolvedExpression,
+      },
+      consteval::Constant,
+      program::Program,
+    },
+  },
+  ir::{
+    function::FunctionId,
+    instruction::{Comparison, Instruction},
+    location::DataLocation,
+    register::{Register, OBJECTIVE},
+    value::Value,
+  },
+  report::{location::WithLineInfo, message::Message},
+};
+use serde_json::{json, Value as Json};
+use std::{collections::HashMap, mem};
+
+/// Register in which a block leaves the value of a `return` it executed
+const BLOCK_RESULT: Register = Register::T(0);
+
+/// What a block returns depending on how it ended
+const FELL_THROUGH: i32 = 0;
+const RETURNED: i32 = 1;
+const BROKE: i32 = 2;
+const CONTINUED: i32 = 3;
+
+/// T registers an operator may use at once, its operands included, which
+/// `fixed_mul` reaches. Expressions start evaluating with at least as many
+/// free registers, spilling the values they hold otherwise.
+const OPERATOR_REGISTERS: usize = 5;
+
+/// Lowers the body of a single function into IR instructions
+pub struct FunctionBuilder<'a> {
+  generator: &'a CodeGenerator,
+  program: &'a Program,
+  id: FunctionId,
+  returns_void: bool,
+  // Values that do not fit in a register are returned in the call storage
+  returns_compound: bool,
+  allocator: RegisterAllocator,
+  locals: HashMap<Name, Value>,
+  types: HashMap<Name, Type<FullIdentifier>>,
+  // Number of data temporaries handed out, see CallStack::temporary
+  data_temporaries: usize,
+  instructions: Vec<Instruction>,
+  returned: bool,
+  // Blocks are compiled to functions of their own, sharing the frame of the
+  // function they belong to. They return how they ended, see FELL_THROUGH.
+  blocks: Vec<McFunction>,
+  block_count: usize,
+  depth: usize,
+}
+
+impl<'a> FunctionBuilder<'a> {
+  pub fn new(
+    generator: &'a CodeGenerator,
+    program: &'a Program,
+    id: FunctionId,
+  ) -> Self {
+    Self {
+      generator,
+      program,
+      id,
+      returns_void: true,
+      returns_compound: false,
+      allocator: RegisterAllocator::new(),
+      locals: HashMap::new(),
+      types: HashMap::new(),
+      data_temporaries: 0,
+      instructions: Vec::new(),
+      returned: false,
+      blocks: Vec::new(),
+      block_count: 0,
+      depth: 0,
+    }
+  }
+
+  /// Builds the function, followed by the functions of its blocks
+  pub fn build(mut self, decl: &FnDecl) -> Vec<McFunction> {
+    self.returns_void = decl.returns_void();
+    self.returns_compound = !decl.returns_void() && !is_scalar(&decl.ret_type);
+    let params = decl.params.iter().map(|param| (&param.name, &param.typ));
+    let locals = decl.locals.iter().map(|local| {
+      let typ = local.typ.as_ref().unwrap_or_else(|| {
+        Message::compiler_bug(&format!("Untyped local `{}`", local.name))
+          .report_and_exit(1)
+      });
+      (&local.name, typ)
+    });
+    for (name, typ) in params.chain(locals) {
+      let saved = is_scalar(typ).then(|| self.allocator.saved()).flatten();
+      let location = match saved {
+        Some(reg) => Value::Register(reg),
+        None => Value::Data(CallStack::local(name)),
+      };
+      self.locals.insert(name.clone(), location);
+      self.types.insert(name.clone(), typ.clone());
+    }
+
+    self.prologue(decl);
+    for statement in &decl.body {
+      if self.returned {
+        break;
+      }
+      self.statement(statement);
+    }
+    if !self.returned {
+      self.epilogue();
+    }
+    let mut function = McFunction::new(self.id);
+    function.instructions = self.instructions;
+    let mut functions = vec![function];
+    functions.extend(self.blocks);
+    functions
+  }
+
+  /// Pushes the stack frame, saves the S registers this function uses and
+  /// moves parameters to their local variables
+  fn prologue(&mut self, decl: &FnDecl) {
+    let frame =
+      CallStack::new_frame(&self.id.to_string(), &self.generator.namespace);
+    self
+      .instructions
+      .push(Instruction::DataPrepend(CallStack::frames(), frame));
+    for reg in self.allocator.used_saved() {
+      self
+        .instructions
+        .push(Instruction::Store(CallStack::postsaved(reg), reg));
+    }
+    let passing = parameter_passing(&decl.params);
+    for (param, passing) in decl.params.iter().zip(passing) {
+      let local = self.locals[&param.name].clone();
+      self.instructions.push(match (passing, local) {
+        (Passing::Register(src), Value::Register(dst)) => {
+          Instruction::Move(dst, src)
+        }
+        (Passing::Register(src), Value::Data(dst)) => {
+          Instruction::Store(dst, src)
+        }
+        (Passing::Storage(index), Value::Register(dst)) => {
+          Instruction::Load(dst, CallStack::parameter(index))
+        }
+        (Passing::Storage(index), Value::Data(dst)) => {
+          Instruction::DataCopy(dst, CallStack::parameter(index))
+        }
+        (_, Value::Constant(_)) => {
+          Message::compiler_bug("Parameter resolved to a constant")
+            .report_and_exit(1)
+        }
+      });
+    }
+  }
+
+  /// Restores the caller's S registers and pops the stack frame
+  fn epilogue(&mut self) {
+    for reg in self.allocator.used_saved() {
+      self
+        .instructions
+        .push(Instruction::Load(reg, CallStack::postsaved(reg)));
+    }
+    self
+      .instructions
+      .push(Instruction::DataRemove(CallStack::frame()));
+  }
+
+  fn statement(&mut self, statement: &FnStatement) {
+    match statement {
+      FnStatement::VarDecl { name, val, .. } => {
+        let target =
+          Expression::AtomIdentifier(name.clone().map(FullIdentifier::Local));
+        self.assignment(&target, AssignOp::Identity, val);
+      }
+      FnStatement::Assignment { target, op, val } => {
+        self.assignment(target, op.value, val);
+      }
+      FnStatement::SideEffect(Expression::Call(target, args)) => {
+        self.call(&target.value, args, false);
+      }
+      FnStatement::SideEffect(expr) if is_scalar(&self.type_of(expr)) => {
+        let reg = self.expression(expr);
+        self.allocator.free(reg);
+      }
+      FnStatement::SideEffect(expr) => {
+        self.compound(expr);
+      }
+      FnStatement::Return(expr) => {
+        let reg = match &expr.value {
+          Some(expr) if self.returns_compound => {
+            let src = self.compound(expr);
+            self
+              .instructions
+              .push(Instruction::DataCopy(CallStack::return_value(), src));
+            None
+          }
+          Some(expr) => Some(self.expression(expr)),
+          None => None,
+        };
+        self.epilogue();
+        if self.depth == 0 {
+          let value = reg.map_or(Value::Constant(0), Value::Register);
+          self.instructions.push(Instruction::Return(value));
+        } else {
+          if let Some(reg) = reg.filter(|reg| *reg != BLOCK_RESULT) {
+            self.instructions.push(Instruction::Move(BLOCK_RESULT, reg));
+          }
+          self
+            .instructions
+            .push(Instruction::Return(Value::Constant(RETURNED)));
+        }
+        if let Some(reg) = reg {
+          self.allocator.free(reg);
+        }
+        self.returned = true;
+      }
+      FnStatement::If {
+        cond,
+        then,
+        otherwise,
+      } => self.branch(cond, then, otherwise),
+      FnStatement::Loop { cond, body, step } => {
+        self.looping(cond.as_ref(), body, step);
+        self.returned = statement.always_jumps();
+      }
+      FnStatement::Break => {
+        self
+          .instructions
+          .push(Instruction::Return(Value::Constant(BROKE)));
+        self.returned = true;
+      }
+      FnStatement::Continue => {
+        self
+          .instructions
+          .push(Instruction::Return(Value::Constant(CONTINUED)));
+        self.returned = true;
+      }
+    }
+  }
+
+  fn branch(
+    &mut self,
+    cond: &ResolvedExpression,
+    then: &[FnStatement],
+    otherwise: &[FnStatement],
+  ) {
+    let name = format!("if{}", self.block_count);
+    self.block_count += 1;
+    let then_id = self.block(&format!("{name}-then"), then);
+    let else_id = (!otherwise.is_empty())
+      .then(|| self.block(&format!("{name}-else"), otherwise));
+
+    let condition = self.expression(cond);
+    let flag = self.flag(may_jump(then) || may_jump(otherwise));
+    let call = |function, result| Instruction::Call {
+      function,
+      context: None,
+      result,
+    };
+
+    match else_id {
+      // Running the then branch may overwrite the condition, so a selector
+      // function makes sure only one of the branches runs
+      Some(else_id) => {
+        let mut selector = McFunction::new(self.id.block(&name));
+        selector.instructions = vec![
+          Instruction::If(
+            condition,
+            1,
+            Box::new(Instruction::ReturnRun(Box::new(call(then_id, None)))),
+          ),
+          Instruction::ReturnRun(Box::new(call(else_id, None))),
+        ];
+        self.instructions.push(call(selector.id.clone(), flag));
+        self.blocks.push(selector);
+      }
+      None => {
+        // The flag is left untouched when the branch is not taken
+        if let Some(flag) = flag {
+          self.instructions.push(Instruction::Set(flag, FELL_THROUGH));
+        }
+        self.instructions.push(Instruction::If(
+          condition,
+          1,
+          Box::new(call(then_id, flag)),
+        ));
+      }
+    }
+    self.allocator.free(condition);
+
+    if let Some(flag) = flag {
+      self.propagate(flag);
+      self.returned = always_jumps(then) && always_jumps(otherwise);
+    }
+  }
+
+  /// Compiles a loop to a function running one iteration then calling
+  /// itself, so that each iteration only costs a bounded number of commands
+  /// on top of its body
+  fn looping(
+    &mut self,
+    cond: Option<&ResolvedExpression>,
+    body: &[FnStatement],
+    step: &[FnStatement],
+  ) {
+    let name = format!("loop{}", self.block_count);
+    self.block_count += 1;
+    let body_id = self.block(&format!("{name}-body"), body);
+    let id = self.id.block(&name);
+    let iteration = id.clone();
+    self.block_with(&name, |this| {
+      if let Some(cond) = cond {
+        let condition = this.expression(cond);
+        this.instructions.push(Instruction::Unless(
+          condition,
+          1,
+          Box::new(Instruction::Return(Value::Constant(FELL_THROUGH))),
+        ));
+        this.allocator.free(condition);
+      }
+      let flag = this.flag(may_jump(body));
+      this.instructions.push(Instruction::Call {
+        function: body_id,
+        context: None,
+        result: flag,
+      });
+      if let Some(flag) = flag {
+        // A `continue` only skips the rest of the body
+        for (code, result) in [(RETURNED, RETURNED), (BROKE, FELL_THROUGH)] {
+          this.instructions.push(Instruction::If(
+            flag,
+            code,
+            Box::new(Instruction::Return(Value::Constant(result))),
+          ));
+        }
+        this.allocator.free(flag);
+      }
+      for statement in step {
+        this.statement(statement);
+      }
+      this.instructions.push(Instruction::ReturnRun(Box::new(
+        Instruction::Call {
+          function: iteration,
+          context: None,
+          result: None,
+        },
+      )));
+      this.returned = true;
+    });
+
+    let flag = self.flag(may_return(body));
+    self.instructions.push(Instruction::Call {
+      function: id,
+      context: None,
+      result: flag,
+    });
+    if let Some(flag) = flag {
+      self.propagate(flag);
+    }
+  }
+
+  /// Allocates a register to hold the result of a block, if it may be
+  /// anything else than FELL_THROUGH
+  fn flag(&mut self, needed: bool) -> Option<Register> {
+    if !needed {
+      return None;
+    }
+    // The flag must not be overwritten by the value the block returns
+    let reserved = !self.allocator.live().contains(&BLOCK_RESULT);
+    if reserved {
+      self.allocator.take(BLOCK_RESULT);
+    }
+    let flag = self.allocator.temporary();
+    if reserved {
+      self.allocator.free(BLOCK_RESULT);
+    }
+    Some(flag)
+  }
+
+  /// Ends the current function early if the block whose result is in `flag`
+  /// did not fall through, then frees `flag`
+  fn propagate(&mut self, flag: Register) {
+    // Blocks pass any result on to the function they belong to, which only
+    // sees RETURNED since loops handle breaks and continues themselves
+    let instruction = if self.depth > 0 {
+      Instruction::Unless(
+        flag,
+        FELL_THROUGH,
+        Box::new(Instruction::Return(Value::Register(flag))),
+      )
+    } else {
+      let value = if self.returns_void || self.returns_compound {
+        Value::Constant(0)
+      } else {
+        Value::Register(BLOCK_RESULT)
+      };
+      Instruction::If(flag, RETURNED, Box::new(Instruction::Return(value)))
+    };
+    self.instructions.push(instruction);
+    self.allocator.free(flag);
+  }
+
+  /// Compiles a block to a function of its own, returning its id
+  fn block(&mut self, suffix: &str, body: &[FnStatement]) -> FunctionId {
+    self.block_with(suffix, |this| {
+      for statement in body {
+        if this.returned {
+          break;
+        }
+        this.statement(statement);
+      }
+    })
+  }
+
+  /// Compiles the instructions emitted by `build` to a function of its own,
+  /// which falls through unless `build` ends it
+  fn block_with(
+    &mut self,
+    suffix: &str,
+    build: impl FnOnce(&mut Self),
+  ) -> FunctionId {
+    let instructions = mem::take(&mut self.instructions);
+    let returned = mem::replace(&mut self.returned, false);
+    self.depth += 1;
+    build(self);
+    if !self.returned {
+      self
+        .instructions
+        .push(Instruction::Return(Value::Constant(FELL_THROUGH)));
+    }
+    self.depth -= 1;
+    self.returned = returned;
+
+    let mut block = McFunction::new(self.id.block(suffix));
+    block.instructions = mem::replace(&mut self.instructions, instructions);
+    let id = block.id.clone();
+    self.blocks.push(block);
+    id
+  }
+
+  fn assignment(
+    &mut self,
+    target: &ResolvedExpression,
+    op: AssignOp,
+    val: &ResolvedExpression,
+  ) {
+    let mut write_backs = Vec::new();
+    let location =
+      self.place(target, op != AssignOp::Identity, &mut write_backs);
+    self.assign_to(location, op, val);
+    self.write_back(write_backs);
+  }
+
+  /// Puts back the elements at runtime indices that `place` copied, from the
+  /// innermost one
+  fn write_back(
+    &mut self,
+    write_backs: Vec<(DataLocation, Register, DataLocation)>,
+  ) {
+    for (list, index, copy) in write_backs.into_iter().rev() {
+      self.element_at(list, index, |dst| Instruction::DataCopy(dst, copy));
+      self.allocator.free(index);
+    }
+  }
+
+  /// Location of an assignment target. An element at a runtime index cannot
+  /// be reached by a path, so it is copied to a data temporary and pushed to
+  /// `write_backs` along with its list and the register holding its index.
+  /// The copy is left empty if `reads` is false and the whole element is
+  /// assigned.
+  fn place(
+    &mut self,
+    target: &ResolvedExpression,
+    reads: bool,
+    write_backs: &mut Vec<(DataLocation, Register, DataLocation)>,
+  ) -> Value {
+    let data = |value| match value {
+      Value::Data(location) => location,
+      _ => {
+        Message::compiler_bug("Accessed a part of a scalar").report_and_exit(1)
+      }
+    };
+    match target {
+      Expression::AtomIdentifier(id) => self.location(&id.value),
+      Expression::Field(base, name) => {
+        let base = data(self.place(base, true, write_backs));
+        Value::Data(base.child(&name.value))
+      }
+      Expression::Index(base, index) => {
+        let list = data(self.place(base, true, write_backs));
+        if let Some(index) = constant_index(index) {
+          return Value::Data(list.index(&index.to_string()));
+        }
+        let reg = self.expression(index);
+        let copy = self.data_temporary();
+        if reads {
+          let dst = copy.clone();
+          self.element_at(list.clone(), reg, |src| {
+            Instruction::DataCopy(dst, src)
+          });
+        }
+        write_backs.push((list, reg, copy.clone()));
+        Value::Data(copy)
+      }
+      _ => Message::compiler_bug("Assigned to a temporary value")
+        .report_and_exit(1),
+    }
+  }
+
+  fn assign_to(
+    &mut self,
+    location: Value,
+    op: AssignOp,
+    val: &ResolvedExpression,
+  ) {
+    if !is_scalar(&self.type_of(val)) {
+      let Value::Data(dst) = location else {
+        Message::compiler_bug("Stored a compound value in a register")
+          .report_and_exit(1)
+      };
+      let mut src = self.compound(val);
+      if op == AssignOp::Add {
+        src = self.concat(dst.clone(), src);
+      }
+      if src != dst {
+        self.instructions.push(Instruction::DataCopy(dst, src));
+      }
+      return;
+    }
+
+    let float = self.type_of(val) == Type::Builtin(BuiltinType::Float);
+    let src = self.expression(val);
+    match location {
+      Value::Register(dst) => self.assign(dst, op, src, float),
+      Value::Data(location) if op == AssignOp::Identity => {
+        self.instructions.push(Instruction::Store(location, src));
+      }
+      Value::Data(location) => {
+        let dst = self.allocator.temporary();
+        self
+          .instructions
+          .push(Instruction::Load(dst, location.clone()));
+        self.assign(dst, op, src, float);
+        self.instructions.push(Instruction::Store(location, dst));
+        self.allocator.free(dst);
+      }
+      Value::Constant(_) => {
+        Message::compiler_bug("Assignment target resolved to a constant")
+          .report_and_exit(1)
+      }
+    }
+    self.allocator.free(src);
+  }
+
+  fn assign(
+    &mut self,
+    dst: Register,
+    op: AssignOp,
+    src: Register,
+    float: bool,
+  ) {
+    match op {
+      AssignOp::Mul if float => return self.fixed_mul(dst, src),
+      AssignOp::Div if float => return self.fixed_div(dst, src),
+      _ => {}
+    }
+    self.instructions.push(match op {
+      AssignOp::Identity => Instruction::Move(dst, src),
+      AssignOp::Add => Instruction::Add(dst, src),
+      AssignOp::Sub => Instruction::Sub(dst, src),
+      AssignOp::Mul => Instruction::Mul(dst, src),
+      AssignOp::Div => Instruction::Div(dst, src),
+      AssignOp::Mod => Instruction::Mod(dst, src),
+    });
+  }
+
+  fn location(&self, id: &FullIdentifier) -> Value {
+    match id {
+      FullIdentifier::Local(name) => {
+        self.locals.get(name).cloned().unwrap_or_else(|| {
+          Message::compiler_bug(&format!("Unknown local variable `{name}`"))
+            .report_and_exit(1)
+        })
+      }
+      FullIdentifier::Global(path) => {
+        Value::Data(self.generator.global_location(path))
+      }
+    }
+  }
+
+  /// Evaluates a value that does not fit in a register, returning where it
+  /// lives. Variables and their fields are used in place.
+  fn compound(&mut self, expr: &ResolvedExpression) -> DataLocation {
+    match expr {
+      Expression::AtomIdentifier(id) => match self.location(&id.value) {
+        Value::Data(location) => location,
+        _ => Message::compiler_bug("Compound variable held in a register")
+          .report_and_exit(1),
+      },
+      Expression::Field(base, name) => self.compound(base).child(&name.value),
+      // Literals are built apart, since their fields may read the variable
+      // they are assigned to
+      Expression::Struct(_, fields) => {
+        let temporary = self.data_temporary();
+        for (name, val) in fields {
+          self.store(temporary.child(&name.value), val);
+        }
+        temporary
+      }
+      Expression::Array(_, elements) => {
+        let temporary = self.data_temporary();
+        self
+          .instructions
+          .push(Instruction::DataSet(temporary.clone(), String::from("[]")));
+        for element in elements {
+          self.append(temporary.clone(), element);
+        }
+        temporary
+      }
+      Expression::Index(base, index) => {
+        let list = self.compound(base);
+        match constant_index(index) {
+          Some(index) => list.index(&index.to_string()),
+          None => {
+            let temporary = self.data_temporary();
+            let dst = temporary.clone();
+            self.element(list, index, |src| Instruction::DataCopy(dst, src));
+            temporary
+          }
+        }
+      }
+      Expression::Call(target, args) => {
+        match self.call(&target.value, args, true) {
+          Some(Value::Data(location)) => location,
+          _ => Message::compiler_bug("Compound call result held in a register")
+            .report_and_exit(1),
+        }
+      }
+      Expression::AtomString(value) => {
+        let temporary = self.data_temporary();
+        self
+          .instructions
+          .push(Instruction::DataSet(temporary.clone(), quote(&value.value)));
+        temporary
+      }
+      Expression::BinOp(left, op, right) if op.value == BinOp::Add => {
+        let left = self.compound(left);
+        let right = self.compound(right);
+        self.concat(left, right)
+      }
+      _ => Message::compiler_bug("Expression has no compound value")
+        .report_and_exit(1),
+    }
+  }
+
+  /// Evaluates `expr` into a data location
+  fn store(&mut self, dst: DataLocation, expr: &ResolvedExpression) {
+    if is_scalar(&self.type_of(expr)) {
+      let reg = self.expression(expr);
+      self.instructions.push(Instruction::Store(dst, reg));
+      self.allocator.free(reg);
+    } else {
+      let src = self.compound(expr);
+      self.instructions.push(Instruction::DataCopy(dst, src));
+    }
+  }
+
+  /// Evaluates `expr` onto the end of the list at `list`
+  fn append(&mut self, list: DataLocation, expr: &ResolvedExpression) {
+    if is_scalar(&self.type_of(expr)) {
+      let reg = self.expression(expr);
+      self
+        .instructions
+        .push(Instruction::DataAppend(list.clone(), String::from("0")));
+      self
+        .instructions
+        .push(Instruction::Store(list.index("-1"), reg));
+      self.allocator.free(reg);
+    } else {
+      let src = self.compound(expr);
+      self
+        .instructions
+        .push(Instruction::DataAppendFrom(list, src));
+    }
+  }
+
+  /// Concatenates two strings into a temporary. Commands cannot join strings,
+  /// but a function macro can write them next to each other in a literal.
+  fn concat(
+    &mut self,
+    left: DataLocation,
+    right: DataLocation,
+  ) -> DataLocation {
+    let temporary = self.data_temporary();
+    let instruction =
+      Instruction::DataSet(temporary.clone(), String::from("\"$(a)$(b)\""));
+    let args = [("a", Value::Data(left)), ("b", Value::Data(right))];
+    self.macro_call("concat", &args, instruction);
+    temporary
+  }
+
+  /// Emits `access` on the element of `list` at a runtime `index`
+  fn element(
+    &mut self,
+    list: DataLocation,
+    index: &ResolvedExpression,
+    access: impl FnOnce(DataLocation) -> Instruction,
+  ) {
+    let reg = self.expression(index);
+    self.element_at(list, reg, access);
+    self.allocator.free(reg);
+  }
+
+  /// Emits `access` on the element of `list` at the index in `index`
+  fn element_at(
+    &mut self,
+    list: DataLocation,
+    index: Register,
+    access: impl FnOnce(DataLocation) -> Instruction,
+  ) {
+    let instruction = access(list.index("$(index)"));
+    self.macro_call("index", &[("index", Value::Register(index))], instruction);
+  }
+
+  /// Runs `instruction` in a function macro of its own, named after `kind`.
+  /// Paths and literals cannot hold a score, so runtime values get into
+  /// them as macro arguments, passed through a call storage.
+  fn macro_call(
+    &mut self,
+    kind: &str,
+    args: &[(&str, Value)],
+    instruction: Instruction,
+  ) {
+    self.instructions.push(Instruction::DataPrepend(
+      CallStack::call_storages(),
+      String::from("{}"),
+    ));
+    for (name, value) in args {
+      let dst = CallStack::call_storage().child(name);
+      self.instructions.push(match value {
+        Value::Register(reg) => Instruction::Store(dst, *reg),
+        Value::Data(src) => Instruction::DataCopy(dst, src.clone()),
+        Value::Constant(value) => Instruction::DataSet(dst, value.to_string()),
+      });
+    }
+
+    let mut helper =
+      McFunction::new(self.id.block(&format!("{kind}{}", self.block_count)));
+    self.block_count += 1;
+    helper.instructions = vec![Instruction::Macro(Box::new(instruction))];
+    self.instructions.push(Instruction::Call {
+      function: helper.id.clone(),
+      context: Some(CallStack::call_storage()),
+      result: None,
+    });
+    self.blocks.push(helper);
+    self
+      .instructions
+      .push(Instruction::DataRemove(CallStack::call_storage()));
+  }
+
+  fn data_temporary(&mut self) -> DataLocation {
+    self.data_temporaries += 1;
+    CallStack::temporary(self.data_temporaries - 1)
+  }
+
+  /// Type of an expression the type checker accepted
+  fn type_of(&self, expr: &ResolvedExpression) -> Type<FullIdentifier> {
+    let unknown = |what: &str| -> ! {
+      Message::compiler_bug(&format!("Unknown type of {what}"))
+        .report_and_exit(1)
+    };
+    match expr {
+      Expression::AtomBoolean(_) => Type::Builtin(BuiltinType::Bool),
+      Expression::AtomInteger(_) => Type::Builtin(BuiltinType::Int),
+      Expression::AtomFloat(_) => Type::Builtin(BuiltinType::Float),
+      Expression::AtomString(_) => Type::Builtin(BuiltinType::String),
+      Expression::AtomIdentifier(id) => match &id.value {
+        FullIdentifier::Local(name) => self
+          .types
+          .get(name)
+          .cloned()
+          .unwrap_or_else(|| unknown(&format!("`{name}`"))),
+        FullIdentifier::Global(_) => self
+          .program
+          .global(&id.value)
+          .and_then(|global| global.typ.as_ref())
+          .map(|typ| typ.value.clone())
+          .unwrap_or_else(|| unknown(&format!("`{}`", id.value))),
+      },
+      Expression::Struct(id, _) => Type::Declared(id.value.clone()),
+      Expression::Field(base, name) => match self.type_of(base) {
+        Type::Declared(id) => self
+          .program
+          .structure(&id)
+          .and_then(|decl| decl.field(&name.value))
+          .map(|field| field.typ.clone())
+          .unwrap_or_else(|| unknown(&format!("field `{}`", name.value))),
+        _ => unknown(&format!("field `{}`", name.value)),
+      },
+      Expression::Array(_, elements) => Type::Array(Box::new(
+        elements
+          .first()
+          .map_or_else(Type::void, |element| self.type_of(element)),
+      )),
+      Expression::Index(base, _) => match self.type_of(base) {
+        Type::Array(element) => *element,
+        _ => unknown("indexed value"),
+      },
+      // Calls to functions that were not compiled are reported by `call`
+      Expression::Call(target, args) => match &target.value {
+        CallTarget::Declared(path) => self
+          .program
+          .function(&path.value)
+          .map_or_else(Type::void, |callee| callee.ret_type.clone()),
+        CallTarget::Builtin(BuiltinFn::Len) => Type::Builtin(BuiltinType::Int),
+        CallTarget::Builtin(BuiltinFn::Substring) => Type::string(),
+        CallTarget::Builtin(BuiltinFn::Pop) => match self.type_of(&args[0]) {
+          Type::Array(element) => *element,
+          _ => unknown("popped value"),
+        },
+        CallTarget::Builtin(_) => Type::void(),
+      },
+      Expression::UnOp(_, operand) => self.type_of(operand),
+      Expression::Cast(_, typ) => Type::Builtin(typ.value),
+      Expression::BinOp(left, op, _) => match op.value {
+        BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => {
+          self.type_of(left)
+        }
+        op if is_bitwise(op) => self.type_of(left),
+        _ => Type::Builtin(BuiltinType::Bool),
+      },
+    }
+  }
+
+  /// Evaluates `expr` into a fresh temporary register
+  fn expression(&mut self, expr: &ResolvedExpression) -> Register {
+    match expr {
+      Expression::AtomBoolean(value) => {
+        let reg = self.allocator.temporary();
+        self
+          .instructions
+          .push(Instruction::Set(reg, value.value as i32));
+        reg
+      }
+      Expression::AtomInteger(value) => {
+        let reg = self.allocator.temporary();
+        // Checked by the type checker
+        let constant = i32::try_from(value.value).unwrap_or_else(|_| {
+          Message::compiler_bug(&format!(
+            "Integer literal `{}` does not fit in a scoreboard",
+            value.value
+          ))
+          .report_and_exit(1)
+        });
+        self.instructions.push(Instruction::Set(reg, constant));
+        reg
+      }
+      Expression::AtomFloat(value) => {
+        let reg = self.allocator.temporary();
+        let constant = self.fixed(value.value);
+        self.instructions.push(Instruction::Set(reg, constant));
+        reg
+      }
+      Expression::AtomString(_) => {
+        Message::compiler_bug("String evaluated into a register")
+          .report_and_exit(1)
+      }
+      Expression::AtomIdentifier(id) => {
+        let reg = self.allocator.temporary();
+        self.instructions.push(match self.location(&id.value) {
+          Value::Register(src) => Instruction::Move(reg, src),
+          Value::Data(location) => Instruction::Load(reg, location),
+          Value::Constant(value) => Instruction::Set(reg, value),
+        });
+        reg
+      }
+      Expression::Struct(..) | Expression::Array(..) => {
+        Message::compiler_bug("Compound literal evaluated into a register")
+          .report_and_exit(1)
+      }
+      Expression::Index(base, index) => {
+        let list = self.compound(base);
+        match constant_index(index) {
+          Some(index) => {
+            let reg = self.allocator.temporary();
+            self
+              .instructions
+              .push(Instruction::Load(reg, list.index(&index.to_string())));
+            reg
+          }
+          None => {
+            let index = self.expression(index);
+            let reg = self.allocator.temporary();
+            self.element_at(list, index, |src| Instruction::Load(reg, src));
+            self.allocator.free(index);
+            reg
+          }
+        }
+      }
+      Expression::Field(base, name) => {
+        let location = self.compound(base).child(&name.value);
+        let reg = self.allocator.temporary();
+        self.instructions.push(Instruction::Load(reg, location));
+        reg
+      }
+      Expression::Call(target, args) => {
+        match self.call(&target.value, args, true) {
+          Some(Value::Register(reg)) => reg,
+          _ => Message::compiler_bug("Used the result of a void function")
+            .report_and_exit(1),
+        }
+      }
+      Expression::BinOp(left, op, right)
+        if matches!(op.value, BinOp::Equal | BinOp::NotEqual)
+          && !is_scalar(&self.type_of(left)) =>
+      {
+        let lhs = self.compound(left);
+        let copy = self.data_temporary();
+        self
+          .instructions
+          .push(Instruction::DataCopy(copy.clone(), lhs));
+        let rhs = self.compound(right);
+        // Overwriting a copy of the left value only succeeds if they differ
+        let reg = self.allocator.temporary();
+        self.instructions.push(Instruction::StoreSuccess(
+          reg,
+          Box::new(Instruction::DataCopy(copy, rhs)),
+        ));
+        if op.value == BinOp::Equal {
+          self.instructions.push(Instruction::Not(reg, reg));
+        }
+        reg
+      }
+      Expression::UnOp(op, operand) => {
+        let reg = self.expression(operand);
+        match op.value {
+          UnOp::Identity => {}
+          UnOp::Not => self.instructions.push(Instruction::Not(reg, reg)),
+          UnOp::Negate | UnOp::BitNot => {
+            let factor = self.allocator.temporary();
+            self.instructions.push(Instruction::Set(factor, -1));
+            self.instructions.push(Instruction::Mul(reg, factor));
+            self.allocator.free(factor);
+            // `~x` is `-x - 1` in two's complement
+            if op.value == UnOp::BitNot {
+              self.instructions.push(Instruction::AddConstant(reg, -1));
+            }
+          }
+        }
+        reg
+      }
+      Expression::Cast(operand, typ) => {
+        let from = self.type_of(operand);
+        let reg = self.expression(operand);
+        let float = Type::Builtin(BuiltinType::Float);
+        let to = Type::Builtin(typ.value);
+        if from != to {
+          let scale = self.scale();
+          self.instructions.push(if to == float {
+            Instruction::Mul(reg, scale)
+          } else {
+            Instruction::Div(reg, scale)
+          });
+          self.allocator.free(scale);
+        }
+        reg
+      }
+      Expression::BinOp(left, op, right)
+        if matches!(op.value, BinOp::And | BinOp::Or) =>
+      {
+        self.logical(left, op.value, right)
+      }
+      Expression::BinOp(left, op, right) if is_bitwise(op.value) => {
+        self.bitwise(left, op.value, right)
+      }
+      Expression::BinOp(left, op, right) => {
+        let lhs = self.expression(left);
+        let constant = match right.as_ref() {
+          Expression::AtomInteger(value) => i32::try_from(value.value).ok(),
+          Expression::AtomFloat(value) => Some(self.fixed(value.value)),
+          _ => None,
+        };
+        let constant = match op.value {
+          BinOp::Add => constant,
+          BinOp::Sub => constant.and_then(i32::checked_neg),
+          _ => None,
+        };
+        // `add` and `remove` take a magnitude, and the one of `i32::MIN` is
+        // not an int, so it is set in a register like any other operand
+        if let Some(constant) = constant.filter(|&value| value != i32::MIN) {
+          self
+            .instructions
+            .push(Instruction::AddConstant(lhs, constant));
+          return lhs;
+        }
+        let mut held = [lhs];
+        let rhs = self.holding(&mut held, right, true);
+        let [lhs] = held;
+        let float = self.type_of(left) == Type::Builtin(BuiltinType::Float);
+        if float && matches!(op.value, BinOp::Mul | BinOp::Div) {
+          if op.value == BinOp::Mul {
+            self.fixed_mul(lhs, rhs);
+          } else {
+            self.fixed_div(lhs, rhs);
+          }
+          self.release(rhs);
+          return lhs;
+        }
+        let comparison = |cmp| Instruction::Compare(lhs, lhs, cmp, rhs);
+        self.instructions.push(match op.value {
+          BinOp::Add => Instruction::Add(lhs, rhs),
+          BinOp::Sub => Instruction::Sub(lhs, rhs),
+          BinOp::Mul => Instruction::Mul(lhs, rhs),
+          BinOp::Div => Instruction::Div(lhs, rhs),
+          BinOp::Mod => Instruction::Mod(lhs, rhs),
+          BinOp::Equal => comparison(Comparison::Equal),
+          BinOp::NotEqual => comparison(Comparison::NotEqual),
+          BinOp::Less => comparison(Comparison::Less),
+          BinOp::LessOrEqual => comparison(Comparison::LessOrEqual),
+          BinOp::Greater => comparison(Comparison::Greater),
+          BinOp::GreaterOrEqual => comparison(Comparison::GreaterOrEqual),
+          _ => unreachable!(),
+        });
+        self.release(rhs);
+        lhs
+      }
+    }
+  }
+
+  /// Compiles `&&` and `||`, which only evaluate their right operand when
+  /// the left one does not decide the result. An operand without calls has
+  /// no side effects, so it is evaluated anyway and only its result is
+  /// conditional. Otherwise it goes to a function `<function>-and<n>` or
+  /// `<function>-or<n>` run on the left operand's value.
+  fn logical(
+    &mut self,
+    left: &ResolvedExpression,
+    op: BinOp,
+    right: &ResolvedExpression,
+  ) -> Register {
+    let reg = self.expression(left);
+    let only_if = |reg, instruction| {
+      let instruction = Box::new(instruction);
+      if op == BinOp::And {
+        Instruction::If(reg, 1, instruction)
+      } else {
+        Instruction::Unless(reg, 1, instruction)
+      }
+    };
+    if right.first_call().is_none() {
+      let mut held = [reg];
+      let value = self.holding(&mut held, right, false);
+      let [reg] = held;
+      self
+        .instructions
+        .push(only_if(reg, Instruction::Move(reg, value)));
+      self.allocator.free(value);
+      return reg;
+    }
+
+    let kind = if op == BinOp::And { "and" } else { "or" };
+    let name = format!("{kind}{}", self.block_count);
+    self.block_count += 1;
+    let id = self.block_with(&name, |this| {
+      // The block only runs on a known left value, so its register is free
+      // until the right one is moved there
+      this.allocator.free(reg);
+      let value = this.expression(right);
+      if value != reg {
+        this.instructions.push(Instruction::Move(reg, value));
+        this.allocator.free(value);
+        this.allocator.take(reg);
+      }
+    });
+    self.instructions.push(only_if(
+      reg,
+      Instruction::Call {
+        function: id,
+        context: None,
+        result: None,
+      },
+    ));
+    reg
+  }
+
+  /// Compiles the bitwise operators, which scoreboards lack. Shifts by a
+  /// constant multiply or divide by a power of two, and `&` with a mask of
+  /// the lowest bits takes a remainder. Anything else calls a helper.
+  fn bitwise(
+    &mut self,
+    left: &ResolvedExpression,
+    op: BinOp,
+    right: &ResolvedExpression,
+  ) -> Register {
+    let lhs = self.expression(left);
+    let constant = match right {
+      Expression::AtomInteger(value) => i32::try_from(value.value).ok(),
+      _ => None,
+    };
+    // Keeping the `n` lowest bits is taking the remainder by `2^n`
+    let modulus = constant
+      .filter(|mask| *mask > 0)
+      .and_then(|mask| mask.checked_add(1))
+      .filter(|modulus| modulus.count_ones() == 1);
+    let factors = match (op, constant) {
+      // Shift amounts are taken modulo 32
+      (BinOp::ShiftLeft, Some(amount)) => {
+        vec![1i32.wrapping_shl(amount as u32)]
+      }
+      // `1 << 31` does not fit, but dividing twice rounds the same way
+      (BinOp::ShiftRight, Some(amount)) => {
+        let amount = amount as u32 % 32;
+        vec![1 << amount.min(30), 1 << amount.saturating_sub(30)]
+      }
+      (BinOp::BitAnd, _) => modulus.into_iter().collect(),
+      _ => Vec::new(),
+    };
+    if !factors.is_empty() {
+      let factor = self.allocator.temporary();
+      for value in factors.into_iter().filter(|value| *value != 1) {
+        self.instructions.push(Instruction::Set(factor, value));
+        self.instructions.push(match op {
+          BinOp::ShiftLeft => Instruction::Mul(lhs, factor),
+          BinOp::ShiftRight => Instruction::Div(lhs, factor),
+          _ => Instruction::Mod(lhs, factor),
+        });
+      }
+      self.allocator.free(factor);
+      return lhs;
+    }
+
+    let mut held = [lhs];
+    let rhs = self.holding(&mut held, right, false);
+    let [lhs] = held;
+    let function = self.generator.helper(match op {
+      BinOp::BitAnd => Helper::BitAnd,
+      BinOp::BitOr => Helper::BitOr,
+      BinOp::BitXor => Helper::BitXor,
+      BinOp::ShiftLeft => Helper::ShiftLeft,
+      _ => Helper::ShiftRight,
+    });
+    // Helpers overwrite T registers like any function would
+    let saved: Vec<_> = self
+      .allocator
+      .live()
+      .into_iter()
+      .filter(|reg| *reg != lhs && *reg != rhs)
+      .collect();
+    self.spill(&saved);
+    let (first, second) = (Register::T(0), Register::T(1));
+    if rhs == first && lhs == second {
+      self.instructions.push(Instruction::Swap(first, second));
+    } else if rhs == first {
+      self.instructions.push(Instruction::Move(second, rhs));
+      self.instructions.push(Instruction::Move(first, lhs));
+    } else {
+      if lhs != first {
+        self.instructions.push(Instruction::Move(first, lhs));
+      }
+      if rhs != second {
+        self.instructions.push(Instruction::Move(second, rhs));
+      }
+    }
+    self.instructions.push(Instruction::Call {
+      function,
+      context: None,
+      result: Some(lhs),
+    });
+    self.allocator.free(rhs);
+    self.unspill(&saved);
+    lhs
+  }
+
+  /// Fixed-point representation of a float constant, clamped to the range of
+  /// a scoreboard. The type checker warns about constants out of range.
+  fn fixed(&self, value: f64) -> i32 {
+    (value * f64::from(self.generator.float_scale)).round() as i32
+  }
+
+  /// Allocates a register holding the fixed-point value of `1.0`
+  fn scale(&mut self) -> Register {
+    let reg = self.allocator.temporary();
+    self
+      .instructions
+      .push(Instruction::Set(reg, self.generator.float_scale));
+    reg
+  }
+
+  /// Multiplies two fixed-point values into `dst`. Splitting `dst` into
+  /// `qa * scale + ra` and `src` into `qb * scale + rb` gives
+  /// `dst * src / scale = qa * src + ra * qb + ra * rb / scale`, whose terms
+  /// do not overflow unless the result does.
+  fn fixed_mul(&mut self, dst: Register, src: Register) {
+    let scale = self.scale();
+    let ra = self.allocator.temporary();
+    let part = self.allocator.temporary();
+    self.instructions.extend([
+      Instruction::Move(ra, dst),
+      Instruction::Mod(ra, scale),
+      Instruction::Div(dst, scale),
+      Instruction::Mul(dst, src),
+      Instruction::Move(part, src),
+      Instruction::Div(part, scale),
+      Instruction::Mul(part, ra),
+      Instruction::Add(dst, part),
+      Instruction::Move(part, src),
+      Instruction::Mod(part, scale),
+      Instruction::Mul(part, ra),
+      Instruction::Div(part, scale),
+      Instruction::Add(dst, part),
+    ]);
+    for reg in [scale, ra, part] {
+      self.allocator.free(reg);
+    }
+  }
+
+  /// Divides two fixed-point values into `dst`, as
+  /// `dst * scale / src = (dst / src) * scale + (dst % src) * scale / src`
+  fn fixed_div(&mut self, dst: Register, src: Register) {
+    let scale = self.scale();
+    let remainder = self.allocator.temporary();
+    self.instructions.extend([
+      Instruction::Move(remainder, dst),
+      Instruction::Mod(remainder, src),
+      Instruction::Div(dst, src),
+      Instruction::Mul(dst, scale),
+      Instruction::Mul(remainder, scale),
+      Instruction::Div(remainder, src),
+      Instruction::Add(dst, remainder),
+    ]);
+    for reg in [scale, remainder] {
+      self.allocator.free(reg);
+    }
+  }
+
+  /// Calls a function following the calling convention from design.md,
+  /// returning where its result is if asked for and non void
+  fn call(
+    &mut self,
+    target: &CallTarget<WithLineInfo<FullIdentifier>>,
+    args: &[ResolvedExpression],
+    wants_result: bool,
+  ) -> Option<Value> {
+    let path = match target {
+      CallTarget::Declared(path) => &path.value,
+      CallTarget::Builtin(BuiltinFn::PrintLn) => {
+        self.print(args);
+        return None;
+      }
+      CallTarget::Builtin(bfn) => {
+        return self.builtin(*bfn, args, wants_result);
+      }
+    };
+    // Functions called by kept code are reachable, so `shake` keeps them
+    let callee = self.program.function(path).unwrap_or_else(|| {
+      Message::compiler_bug(&format!(
+        "Calling `{}`, which was removed as unreachable",
+        path.name()
+      ))
+      .report_and_exit(1)
+    });
+    let passing = parameter_passing(&callee.params);
+    let returns_compound =
+      !callee.returns_void() && !is_scalar(&callee.ret_type);
+    let uses_storage = returns_compound
+      || passing
+        .iter()
+        .any(|passing| matches!(passing, Passing::Storage(_)));
+
+    // Values computed so far must survive the call. Spilling them before
+    // evaluating arguments leaves every T register to the arguments.
+    let saved = self.allocator.live();
+    self.spill(&saved);
+    for reg in &saved {
+      self.allocator.free(*reg);
+    }
+
+    if uses_storage {
+      self.instructions.push(Instruction::DataPrepend(
+        CallStack::call_storages(),
+        String::from("{}"),
+      ));
+    }
+    // When the call storage is used anyway, register arguments are staged
+    // there too so that no more than one of them is held at a time
+    let mut arguments = Vec::new();
+    for (index, (arg, passing)) in args.iter().zip(&passing).enumerate() {
+      match passing {
+        Passing::Register(_) if !uses_storage => {
+          let reg = self.holding(&mut arguments, arg, false);
+          arguments.push(reg);
+        }
+        _ => self.store(CallStack::parameter(index), arg),
+      }
+    }
+    for (index, passing) in passing.iter().enumerate() {
+      if let (Passing::Register(reg), true) = (passing, uses_storage) {
+        self
+          .instructions
+          .push(Instruction::Load(*reg, CallStack::parameter(index)));
+      }
+    }
+    for reg in arguments.clone() {
+      self.allocator.free(reg);
+    }
+    // Nothing else is live, so swaps are enough to put arguments where the
+    // callee expects them
+    for index in 0..arguments.len() {
+      let expected = Register::T(index as u8);
+      let actual = arguments[index];
+      if actual != expected {
+        self.instructions.push(Instruction::Swap(expected, actual));
+        if let Some(other) = arguments.iter().position(|reg| *reg == expected) {
+          arguments[other] = actual;
+        }
+        arguments[index] = expected;
+      }
+    }
+
+    for reg in &saved {
+      self.allocator.take(*reg);
+    }
+    let result = if wants_result && !callee.returns_void() && !returns_compound
+    {
+      Some(self.allocator.temporary())
+    } else {
+      None
+    };
+    self.instructions.push(Instruction::Call {
+      function: FunctionId::from_identifier(&self.generator.namespace, path),
+      context: uses_storage.then(CallStack::call_storage),
+      result,
+    });
+    // The call storage is removed below, so the result is moved out of it
+    let result = if wants_result && returns_compound {
+      let temporary = self.data_temporary();
+      self.instructions.push(Instruction::DataCopy(
+        temporary.clone(),
+        CallStack::return_value(),
+      ));
+      Some(Value::Data(temporary))
+    } else {
+      result.map(Value::Register)
+    };
+    self.unspill(&saved);
+    if uses_storage {
+      self
+        .instructions
+        .push(Instruction::DataRemove(CallStack::call_storage()));
+    }
+    result
+  }
+
+  /// Saves T registers in a new entry of the presaved stack, before a call or
+  /// while evaluating an operand needing them
+  fn spill(&mut self, saved: &[Register]) {
+    if !saved.is_empty() {
+      self.instructions.push(Instruction::DataPrepend(
+        CallStack::presaved_stack(),
+        String::from("{}"),
+      ));
+    }
+    for reg in saved {
+      self
+        .instructions
+        .push(Instruction::Store(CallStack::presaved(*reg), *reg));
+    }
+  }
+
+  /// Restores the T registers saved by `spill` once they are free again
+  fn unspill(&mut self, saved: &[Register]) {
+    for reg in saved {
+      self
+        .instructions
+        .push(Instruction::Load(*reg, CallStack::presaved(*reg)));
+    }
+    if !saved.is_empty() {
+      self
+        .instructions
+        .push(Instruction::DataRemove(CallStack::presaved_entry()));
+    }
+  }
+
+  /// Evaluates `expr` while the registers in `held` keep values needed
+  /// afterwards, as an operand with `operand`. With too few registers left
+  /// for `expr`, the T ones among `held` are spilled meanwhile and loaded
+  /// back into free registers, which replace them in `held`.
+  fn holding(
+    &mut self,
+    held: &mut [Register],
+    expr: &ResolvedExpression,
+    operand: bool,
+  ) -> Register {
+    let is_atom = matches!(
+      expr,
+      Expression::AtomBoolean(_)
+        | Expression::AtomInteger(_)
+        | Expression::AtomFloat(_)
+        | Expression::AtomIdentifier(_)
+    );
+    let spilled: Vec<_> =
+      if is_atom || self.allocator.available() >= OPERATOR_REGISTERS {
+        Vec::new()
+      } else {
+        held
+          .iter()
+          .copied()
+          .filter(|reg| matches!(reg, Register::T(_)))
+          .collect()
+      };
+    self.spill(&spilled);
+    for reg in &spilled {
+      self.allocator.free(*reg);
+    }
+
+    let value = if operand {
+      self.operand(expr)
+    } else {
+      self.expression(expr)
+    };
+    for reg in held.iter_mut().filter(|reg| spilled.contains(reg)) {
+      let src = CallStack::presaved(*reg);
+      if self.allocator.live().contains(reg) {
+        *reg = self.allocator.temporary();
+      } else {
+        self.allocator.take(*reg);
+      }
+      self.instructions.push(Instruction::Load(*reg, src));
+    }
+    if !spilled.is_empty() {
+      self
+        .instructions
+        .push(Instruction::DataRemove(CallStack::presaved_entry()));
+    }
+    value
+  }
+
+  /// Compiles a call to one of the builtins operating on arrays
+  fn builtin(
+    &mut self,
+    bfn: BuiltinFn,
+    args: &[ResolvedExpression],
+    wants_result: bool,
+  ) -> Option<Value> {
+    // Lists changed in place are reached like assignment targets, so that
+    // elements at runtime indices are written back
+    let mut write_backs = Vec::new();
+    let list = match bfn {
+      BuiltinFn::Push | BuiltinFn::Pop if is_place(&args[0]) => {
+        match self.place(&args[0], true, &mut write_backs) {
+          Value::Data(location) => location,
+          _ => {
+            Message::compiler_bug("List held in a register").report_and_exit(1)
+          }
+        }
+      }
+      _ => self.compound(&args[0]),
+    };
+    let result = match bfn {
+      BuiltinFn::Len if wants_result => {
+        // Getting a list gives its length
+        let reg = self.allocator.temporary();
+        self.instructions.push(Instruction::Load(reg, list));
+        Some(Value::Register(reg))
+      }
+      BuiltinFn::Push => {
+        self.append(list, &args[1]);
+        None
+      }
+      BuiltinFn::Pop => {
+        let last = list.index("-1");
+        let scalar = matches!(
+          self.type_of(&args[0]),
+          Type::Array(element) if is_scalar(&element)
+        );
+        let result = if !wants_result {
+          None
+        } else if scalar {
+          let reg = self.allocator.temporary();
+          self.instructions.push(Instruction::Load(reg, last.clone()));
+          Some(Value::Register(reg))
+        } else {
+          let temporary = self.data_temporary();
+          self
+            .instructions
+            .push(Instruction::DataCopy(temporary.clone(), last.clone()));
+          Some(Value::Data(temporary))
+        };
+        self.instructions.push(Instruction::DataRemove(last));
+        result
+      }
+      BuiltinFn::Substring => {
+        let temporary = self.data_temporary();
+        let bounds = [&args[1], &args[2]].map(constant_index);
+        if let [Some(start), Some(end)] = bounds {
+          self.instructions.push(Instruction::DataSetString(
+            temporary.clone(),
+            list,
+            start.to_string(),
+            Some(end.to_string()),
+          ));
+        } else {
+          let start = self.expression(&args[1]);
+          let end = self.expression(&args[2]);
+          let instruction = Instruction::DataSetString(
+            temporary.clone(),
+            list,
+            String::from("$(start)"),
+            Some(String::from("$(end)")),
+          );
+          let args = [
+            ("start", Value::Register(start)),
+            ("end", Value::Register(end)),
+          ];
+          self.macro_call("substring", &args, instruction);
+          self.allocator.free(start);
+          self.allocator.free(end);
+        }
+        Some(Value::Data(temporary))
+      }
+      BuiltinFn::Len | BuiltinFn::PrintLn => None,
+    };
+    self.write_back(write_backs);
+    result
+  }
+
+  /// Compiles `println` to a `tellraw` whose text component reads every
+  /// value where it is: scores for integers, NBT for everything else
+  fn print(&mut self, args: &[ResolvedExpression]) {
+    let (parts, values) = match args.first() {
+      Some(Expression::AtomString(format)) => (
+        parse_format(&format.value).unwrap_or_else(|_| {
+          Message::compiler_bug("Invalid format string").report_and_exit(1)
+        }),
+        &args[1..],
+      ),
+      _ => (vec![FormatPart::Placeholder; args.len()], args),
+    };
+
+    let texts: Vec<_> = values.iter().map(|value| self.text(value)).collect();
+    let mut texts = texts.into_iter();
+    let mut component = vec![json!("")];
+    for part in parts {
+      match part {
+        FormatPart::Text(text) => component.push(json!(text)),
+        FormatPart::Placeholder => component.extend(texts.next().unwrap()),
+      }
+    }
+    self
+      .instructions
+      .push(Instruction::Tellraw(Json::Array(component).to_string()));
+  }
+
+  /// Text components showing the value of `expr`. Values are all evaluated
+  /// before the message is shown, so computed ones are stored right away
+  /// instead of holding a T register each until then.
+  fn text(&mut self, expr: &ResolvedExpression) -> Vec<Json> {
+    let score = |reg: Register| json!({"score": {"name": reg.to_string(), "objective": OBJECTIVE}});
+    let nbt = |location: DataLocation| json!({"nbt": location.path, "storage": location.storage});
+    match self.type_of(expr) {
+      Type::Builtin(BuiltinType::Int) => {
+        let variable = match expr {
+          Expression::AtomIdentifier(id) => Some(self.location(&id.value)),
+          _ => None,
+        };
+        match variable {
+          Some(Value::Register(reg)) => vec![score(reg)],
+          Some(Value::Data(location)) => vec![nbt(location)],
+          _ => {
+            let reg = self.expression(expr);
+            vec![nbt(self.stored(reg))]
+          }
+        }
+      }
+      Type::Builtin(BuiltinType::Bool) => {
+        let reg = self.operand(expr);
+        let temporary = self.data_temporary();
+        self.instructions.extend([
+          Instruction::DataSet(temporary.clone(), quote("false")),
+          Instruction::If(
+            reg,
+            1,
+            Box::new(Instruction::DataSet(temporary.clone(), quote("true"))),
+          ),
+        ]);
+        self.release(reg);
+        vec![nbt(temporary)]
+      }
+      Type::Builtin(BuiltinType::Float) => {
+        let reg = self.expression(expr);
+        let (sign, integer, fraction) = self.decimal(reg);
+        let mut texts = vec![nbt(sign), nbt(self.stored(integer))];
+        if let Some(fraction) = fraction {
+          texts.extend([json!("."), nbt(fraction)]);
+        }
+        texts
+      }
+      _ => {
+        let location = self.compound(expr);
+        vec![nbt(location)]
+      }
+    }
+  }
+
+  /// Moves the value of the T register `reg` to a new storage temporary,
+  /// freeing `reg`
+  fn stored(&mut self, reg: Register) -> DataLocation {
+    let temporary = self.data_temporary();
+    self
+      .instructions
+      .push(Instruction::Store(temporary.clone(), reg));
+    self.allocator.free(reg);
+    temporary
+  }
+
+  /// Splits the fixed-point value in `value` into the parts of its decimal
+  /// writing: the sign as a string, the integer part in a register, and the
+  /// digits after the point as a string unless the scale is 1. Consumes
+  /// `value`.
+  fn decimal(
+    &mut self,
+    value: Register,
+  ) -> (DataLocation, Register, Option<DataLocation>) {
+    let scale = self.generator.float_scale;
+    let sign = self.data_temporary();
+    let negative = self.allocator.temporary();
+    let constant = self.allocator.temporary();
+    let integer = self.allocator.temporary();
+    self.instructions.extend([
+      Instruction::DataSet(sign.clone(), quote("")),
+      Instruction::Set(constant, 0),
+      Instruction::Compare(negative, value, Comparison::Less, constant),
+      Instruction::If(
+        negative,
+        1,
+        Box::new(Instruction::DataSet(sign.clone(), quote("-"))),
+      ),
+      Instruction::Set(constant, -1),
+      Instruction::If(negative, 1, Box::new(Instruction::Mul(value, constant))),
+      Instruction::Set(constant, scale),
+      Instruction::Move(integer, value),
+      Instruction::Div(integer, constant),
+      Instruction::Mod(value, constant),
+    ]);
+
+    // Enough digits to tell apart every multiple of `1 / scale`
+    let mut precision = 1;
+    while precision < scale {
+      precision *= 10;
+    }
+    let fraction = (scale > 1).then(|| {
+      if precision != scale {
+        // `value * precision / scale`, with `precision` split like in
+        // `fixed_mul` so that it does not overflow
+        let part = self.allocator.temporary();
+        self.instructions.extend([
+          Instruction::Move(part, value),
+          Instruction::Set(constant, precision % scale),
+          Instruction::Mul(part, constant),
+          Instruction::Set(constant, scale),
+          Instruction::Div(part, constant),
+          Instruction::Set(constant, precision / scale),
+          Instruction::Mul(value, constant),
+          Instruction::Add(value, part),
+        ]);
+        self.allocator.free(part);
+      }
+      // Leading zeros are kept by writing `precision + value` and cutting
+      // its first digit
+      let digits = self.data_temporary();
+      let fraction = self.data_temporary();
+      self.instructions.extend([
+        Instruction::AddConstant(value, precision),
+        Instruction::Store(digits.clone(), value),
+        Instruction::DataSetString(
+          fraction.clone(),
+          digits,
+          String::from("1"),
+          None,
+        ),
+      ]);
+      fraction
+    });
+    for reg in [value, negative, constant] {
+      self.allocator.free(reg);
+    }
+    (sign, integer, fraction)
+  }
+
+  /// Like `expression`, but reads local variables in place instead of
+  /// copying them. The result must be given back with `release`.
+  fn operand(&mut self, expr: &ResolvedExpression) -> Register {
+    if let Expression::AtomIdentifier(id) = expr {
+      if let Value::Register(reg) = self.location(&id.value) {
+        return reg;
+      }
+    }
+    self.expression(expr)
+  }
+
+  fn release(&mut self, reg: Register) {
+    if let Register::T(_) = reg {
+      self.allocator.free(reg);
+    }
+  }
+}
+
+/// SNBT string literal holding `value`
+fn quote(value: &str) -> String {
+  let escaped = value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n");
+  format!("\"{escaped}\"")
+}
+
+/// SNBT writing of a value known at compile time, laid out like the same
+/// value stored at runtime. Booleans are stored as integers by `Store`.
+pub fn snbt(value: &Constant) -> String {
+  match value {
+    Constant::Bool(value) => i32::from(*value).to_string(),
+    Constant::Int(value) | Constant::Float(value) => value.to_string(),
+    Constant::String(value) => quote(value),
+    Constant::Array(elements) => {
+      let elements: Vec<_> = elements.iter().map(snbt).collect();
+      format!("[{}]", elements.join(","))
+    }
+    Constant::Struct(fields) => {
+      let fields: Vec<_> = fields
+        .iter()
+        .map(|(name, value)| format!("{name}:{}", snbt(value)))
+        .collect();
+      format!("{{{}}}", fields.join(","))
+    }
+  }
+}
+
+/// Whether an expression designates a part of a variable
+fn is_place(expr: &ResolvedExpression) -> bool {
+  match expr {
+    Expression::AtomIdentifier(_) => true,
+    Expression::Field(base, _) | Expression::Index(base, _) => is_place(base),
+    _ => false,
+  }
+}
+
+fn is_bitwise(op: BinOp) -> bool {
+  matches!(
+    op,
+    BinOp::BitAnd
+      | BinOp::BitOr
+      | BinOp::BitXor
+      | BinOp::ShiftLeft
+      | BinOp::ShiftRight
+  )
+}
+
+/// Value of an index known at compile time, which can be part of a path
+fn constant_index(index: &ResolvedExpression) -> Option<i64> {
+  match index {
+    Expression::AtomInteger(value) => Some(value.value as i64),
+    Expression::UnOp(op, operand) if op.value == UnOp::Negate => {
+      constant_index(operand).map(|value| -value)
+    }
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::testing::{commands, compile, run, FLOAT_SCALE};
+
+  /// Commands writing a temporary copy back into `a` at a runtime index
+  const WRITE_BACK: &str =
+    "$data modify storage lc:data frames[0].locals.a[$(index)] set from";
+
+  #[test]
+  fn push_to_runtime_index_is_written_back() {
+    let functions = compile(
+      "#[export]
+fn main(i: int) {
+  let mut a = [[1], [2]];
+  push(a[i], 3);
+}",
+    );
+    let write_backs = functions
+      .values()
+      .filter(|commands| commands.contains(WRITE_BACK))
+      .count();
+    assert_eq!(write_backs, 1, "{functions:#?}");
+  }
+
+  #[test]
+  fn pop_from_runtime_index_is_written_back() {
+    let functions = compile(
+      "#[export]
+fn main(i: int) -> int {
+  let mut a = [[1], [2]];
+  return pop(a[i]);
+}",
+    );
+    let write_backs = functions
+      .values()
+      .filter(|commands| commands.contains(WRITE_BACK))
+      .count();
+    assert_eq!(write_backs, 1, "{functions:#?}");
+  }
+
+  /// Result of the float function `name` taking `a` and `b`
+  fn float_operation(name: &str, a: f64, b: f64) -> f64 {
+    let source = "#[export]
+fn mul(a: float, b: float) -> float {
+  return a * b;
+}
+#[export]
+fn div(a: float, b: float) -> float {
+  return a / b;
+}";
+    let scale = f64::from(FLOAT_SCALE);
+    let fixed = |value: f64| (value * scale) as i32;
+    f64::from(run(source, name, &[fixed(a), fixed(b)])) / scale
+  }
+
+  #[test]
+  fn fixed_point_products_are_rescaled() {
+    assert_eq!(float_operation("mul", 1.5, 2.25), 3.375);
+    assert_eq!(float_operation("mul", -1.5, 1.5), -2.25);
+    assert_eq!(float_operation("mul", 0.001, 0.5), 0.0);
+    // The product of the scaled values would not fit in a scoreboard
+    assert_eq!(float_operation("mul", 40000.0, 2.0), 80000.0);
+  }
+
+  #[test]
+  fn fixed_point_quotients_are_rescaled() {
+    assert_eq!(float_operation("div", 7.0, 2.0), 3.5);
+    assert_eq!(float_operation("div", 1.0, 3.0), 0.333);
+    assert_eq!(float_operation("div", -1.0, 3.0), -0.334);
+    assert_eq!(float_operation("div", 40000.0, 0.5), 80000.0);
+  }
+
+  #[test]
+  fn else_if_chain() {
+    let source = "#[export]
+fn sign(x: int) -> int {
+  if x < 0 {
+    return -1;
+  } else if x == 0 {
+    return 0;
+  } else {
+    return 1;
+  }
+}";
+    assert_eq!(run(source, "sign", &[-5]), -1);
+    assert_eq!(run(source, "sign", &[0]), 0);
+    assert_eq!(run(source, "sign", &[7]), 1);
+  }
+
+  #[test]
+  fn if_without_else_falls_through() {
+    let source = "#[export]
+fn clamp(x: int) -> int {
+  let mut y = x;
+  if y > 10 {
+    y = 10;
+  }
+  return y;
+}";
+    assert_eq!(run(source, "clamp", &[3]), 3);
+    assert_eq!(run(source, "clamp", &[42]), 10);
+  }
+
+  #[test]
+  fn for_loop_with_break_and_continue() {
+    let source = "#[export]
+fn sum(n: int) -> int {
+  let mut total = 0;
+  for i in 0..n {
+    if i == 3 {
+      continue;
+    }
+    if i == 5 {
+      break;
+    }
+    total += i;
+  }
+  return total;
+}";
+    assert_eq!(run(source, "sum", &[0]), 0);
+    assert_eq!(run(source, "sum", &[4]), 3);
+    assert_eq!(run(source, "sum", &[10]), 7);
+  }
+
+  #[test]
+  fn while_and_loop() {
+    let source = "#[export]
+fn grow(start: int) -> int {
+  let mut i = start;
+  while i < 4 {
+    i += 1;
+  }
+  loop {
+    i *= 2;
+    if i > 20 {
+      break;
+    }
+  }
+  return i;
+}";
+    assert_eq!(run(source, "grow", &[0]), 32);
+    assert_eq!(run(source, "grow", &[6]), 24);
+  }
+
+  #[test]
+  fn return_from_nested_loops() {
+    let source = "#[export]
+fn find(target: int) -> int {
+  for i in 0..10 {
+    for j in 0..10 {
+      if i * j == target {
+        return i * 10 + j;
+      }
+    }
+  }
+  return -1;
+}";
+    assert_eq!(run(source, "find", &[12]), 26);
+    assert_eq!(run(source, "find", &[97]), -1);
+  }
+
+  #[test]
+  fn calls_keep_the_registers_of_the_caller() {
+    let source = "fn double(x: int) -> int {
+  let y = x * 2;
+  return y;
+}
+#[export]
+fn main(a: int, b: int) -> int {
+  let c = double(a);
+  return c + double(b) * 10 + a * 100;
+}";
+    assert_eq!(run(source, "main", &[1, 2]), 142);
+  }
+
+  #[test]
+  fn recursion() {
+    let source = "#[export]
+fn fib(n: int) -> int {
+  if n < 2 {
+    return n;
+  }
+  return fib(n - 1) + fib(n - 2);
+}";
+    assert_eq!(run(source, "fib", &[10]), 55);
+  }
+
+  #[test]
+  fn nested_arithmetic() {
+    let source = "#[export]
+fn f(a: int, b: int) -> int {
+  return (a + b) * (a - b) / 3 % 7 - -a;
+}";
+    // 9 * 5 / 3 % 7 = 1
+    assert_eq!(run(source, "f", &[7, 2]), 8);
+    assert_eq!(run(source, "f", &[-7, 2]), -6);
+    // 6 * -4 / 3 = -8, whose remainder has the sign of the divisor
+    assert_eq!(run(source, "f", &[1, 5]), 7);
+  }
+
+  #[test]
+  fn deeply_nested_operands_are_spilled() {
+    let source = "#[export]
+fn f(a: int, b: int) -> int {
+  return a - (b * (a + (b - (a * (b + (a - (b + (a % (b | a)))))))));
+}
+
+#[export]
+fn g(x: float, y: float) -> float {
+  return x * (y * (x * (y * (x * (y / (x - y / 4.0))))));
+}
+
+fn sum(a: int, b: int, c: int, d: int, e: int, f: int) -> int {
+  return a + b + c + d + e + f;
+}
+
+#[export]
+fn h(a: int) -> int {
+  return sum(a, a * (a + (a * (a + a))), a + (a + (a + (a + (a + a)))), a, a, a);
+}";
+    let f = |a: i32, b: i32| {
+      a - (b * (a + (b - (a * (b + (a - (b + a % (b | a))))))))
+    };
+    for (a, b) in [(3, 5), (-2, 7), (6, -4)] {
+      assert_eq!(run(source, "f", &[a, b]), f(a, b));
+    }
+    // 1.5 * 2 * 1.5 * 2 * 1.5 * 2 / 1
+    assert_eq!(run(source, "g", &[1500, 2000]), 27 * FLOAT_SCALE);
+    // 2 + 2 * 10 + 12 + 3 * 2
+    assert_eq!(run(source, "h", &[2]), 40);
+    assert!(compile(source)["test:f"].contains("presaved"));
+  }
+
+  #[test]
+  fn comparisons() {
+    let source = "#[export]
+fn f(a: int, b: int) -> int {
+  let mut flags = 0;
+  if a < b { flags += 1; }
+  if a <= b { flags += 2; }
+  if a == b { flags += 4; }
+  if a != b { flags += 8; }
+  if a >= b { flags += 16; }
+  if a > b { flags += 32; }
+  return flags;
+}";
+    assert_eq!(run(source, "f", &[1, 2]), 1 + 2 + 8);
+    assert_eq!(run(source, "f", &[2, 2]), 2 + 4 + 16);
+    assert_eq!(run(source, "f", &[3, 2]), 8 + 16 + 32);
+  }
+
+  #[test]
+  fn logic_operators() {
+    let source = "#[export]
+fn and(a: bool, b: bool) -> bool { return a && b; }
+#[export]
+fn or(a: bool, b: bool) -> bool { return a || b; }";
+    for (a, b) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+      assert_eq!(run(source, "and", &[a, b]), a & b);
+      assert_eq!(run(source, "or", &[a, b]), a | b);
+    }
+  }
+
+  #[test]
+  fn right_operand_is_short_circuited() {
+    // `forever` never returns, so it must not be called
+    let source = "fn forever() -> bool { return forever(); }
+#[export]
+fn and(a: bool) -> bool { return a && forever(); }
+#[export]
+fn or(a: bool) -> bool { return a || forever(); }";
+    assert_eq!(run(source, "and", &[0]), 0);
+    assert_eq!(run(source, "or", &[1]), 1);
+  }
+
+  #[test]
+  fn bitwise_operators() {
+    let source = "#[export]
+fn and(a: int, b: int) -> int { return a & b; }
+#[export]
+fn or(a: int, b: int) -> int { return a | b; }
+#[export]
+fn xor(a: int, b: int) -> int { return a ^ b; }
+#[export]
+fn shl(a: int, b: int) -> int { return a << b; }
+#[export]
+fn shr(a: int, b: int) -> int { return a >> b; }
+#[export]
+fn not(a: int) -> int { return ~a; }";
+    let values = [0, 1, -1, 6, -45, 123_456, i32::MIN, i32::MAX];
+    for a in values {
+      assert_eq!(run(source, "not", &[a]), !a, "~{a}");
+      for b in values {
+        assert_eq!(run(source, "and", &[a, b]), a & b, "{a} & {b}");
+        assert_eq!(run(source, "or", &[a, b]), a | b, "{a} | {b}");
+        assert_eq!(run(source, "xor", &[a, b]), a ^ b, "{a} ^ {b}");
+      }
+      // Shift amounts are taken modulo 32
+      for b in [0, 1, 5, 31, 32, 33, -1] {
+        let shl = a.wrapping_shl(b as u32);
+        assert_eq!(run(source, "shl", &[a, b]), shl, "{a} << {b}");
+        let shr = a.wrapping_shr(b as u32);
+        assert_eq!(run(source, "shr", &[a, b]), shr, "{a} >> {b}");
+      }
+    }
+  }
+
+  #[test]
+  fn struct_fields() {
+    let source = "struct P { x: int, y: int }
+#[export]
+fn main(a: int) -> int {
+  let mut p = P { x: a, y: 2 };
+  p.x += 5;
+  return p.x * p.y;
+}";
+    assert_eq!(run(source, "main", &[3]), 16);
+  }
+
+  #[test]
+  fn structs_are_copied() {
+    let source = "struct P { x: int }
+struct Line { start: P, end: P }
+#[export]
+fn main() -> int {
+  let mut a = Line { start: P { x: 1 }, end: P { x: 2 } };
+  let mut b = a;
+  b.start.x = 10;
+  a.end = b.start;
+  b.start.x = 100;
+  return a.start.x + a.end.x * 1000 + b.start.x * 1000000;
+}";
+    assert_eq!(run(source, "main", &[]), 100_010_001);
+  }
+
+  #[test]
+  fn strings_are_concatenated_by_a_macro() {
+    let functions = compile(
+      "#[export]
+fn main(s: string) -> string {
+  return s + \"!\";
+}",
+    );
+    let main = &functions["test:main"];
+    assert!(main.contains("set value \"!\""), "{main}");
+    assert!(
+      main.contains("function test:main-concat0 with storage"),
+      "{main}"
+    );
+    assert_eq!(
+      functions["test:main-concat0"],
+      "$data modify storage lc:data frames[0].temps.t1 set value \"$(a)$(b)\"\n"
+    );
+  }
+
+  #[test]
+  fn string_literals_are_escaped() {
+    let main = commands(
+      "#[export]
+fn main() -> string {
+  return \"say \\\"hi\\\"\";
+}",
+      "main",
+    );
+    assert!(main.contains(r#"set value "say \"hi\"""#), "{main}");
+  }
+
+  /// `tellraw` commands of `main` in `source`
+  fn tellraws(source: &str) -> Vec<String> {
+    commands(source, "main")
+      .lines()
+      .filter(|command| command.starts_with("tellraw"))
+      .map(String::from)
+      .collect()
+  }
+
+  #[test]
+  fn println_interpolates_scores() {
+    let source = "#[export]
+fn main(n: int) {
+  println(\"n = {} {{n}}\", n);
+  println(n);
+}";
+    let score = r#"{"score":{"name":"S0","objective":"registers"}}"#;
+    assert_eq!(
+      tellraws(source),
+      [
+        format!(r#"tellraw @a ["","n = ",{score}," {{n}}"]"#),
+        format!(r#"tellraw @a ["",{score}]"#),
+      ]
+    );
+  }
+
+  #[test]
+  fn println_stores_computed_values() {
+    let source = "#[export]
+fn main(n: int, x: float) {
+  println(
+    \"{} {} {} {} {} {} {} {} {}\",
+    n + 1, n + 2, n + 3, n + 4, n + 5, n + 6, n + 7, x * 2.0, x * 3.0
+  );
+}";
+    let main = commands(source, "main");
+    // Each sum is stored as soon as it is computed, so it is only ever in T0
+    for index in 0..7 {
+      assert!(
+        main.contains(&format!(
+          "execute store result storage lc:data frames[0].temps.t{index} \
+           int 1 run scoreboard players get T0 registers"
+        )),
+        "{main}"
+      );
+    }
+    let tellraw = &tellraws(source)[0];
+    assert!(!tellraw.contains("score"), "{tellraw}");
+    let nbt = |index| {
+      format!(r#"{{"nbt":"frames[0].temps.t{index}","storage":"lc:data"}}"#)
+    };
+    for index in 0..7 {
+      assert!(tellraw.contains(&nbt(index)), "{tellraw}");
+    }
+  }
+
+  #[test]
+  fn println_escapes_text() {
+    let source = "#[export]
+fn main() {
+  println(\"say \\\"hi\\\"\");
+}";
+    assert_eq!(tellraws(source), [r#"tellraw @a ["","say \"hi\""]"#]);
+  }
+
+  #[test]
+  fn println_reads_strings_from_storage() {
+    let source = "#[export]
+fn main(s: string) {
+  println(\"s = {}\", s);
+}";
+    let tellraw = &tellraws(source)[0];
+    assert!(
+      tellraw.contains(r#"{"nbt":"frames[0].locals.s","storage":"lc:data"}"#),
+      "{tellraw}"
+    );
+  }
+
+  #[test]
+  fn constants_are_inlined() {
+    let source = "const N: int = 1 + 2;
+const M: int = N * N;
+#[export]
+fn main() -> int {
+  return M;
+}";
+    assert_eq!(run(source, "main", &[]), 9);
+    let main = commands(source, "main");
+    assert!(
+      main.contains("scoreboard players set T0 registers 9"),
+      "{main}"
+    );
+    assert!(!main.contains("data get"), "{main}");
+  }
+
+  #[test]
+  fn globals_are_initialized_at_load() {
+    let source = "fn f() -> int { return 4; }
+let a = 2 * 3;
+let mut b = f() + a;
+#[export]
+fn main() -> int {
+  b += 1;
+  return a + b;
+}";
+    assert_eq!(run(source, "main", &[]), 17);
+  }
+
+  #[test]
+  fn adding_the_smallest_int() {
+    let source = "#[export]
+fn add(x: int) -> int { return x + -2147483648; }
+#[export]
+fn sub(x: int) -> int { return x - -2147483648; }";
+    for x in [0, 1, -1, i32::MAX, i32::MIN] {
+      let expected = x.wrapping_add(i32::MIN);
+      assert_eq!(run(source, "add", &[x]), expected, "{x} + MIN");
+      assert_eq!(
+        run(source, "sub", &[x]),
+        x.wrapping_sub(i32::MIN),
+        "{x} - MIN"
+      );
+    }
+    let add = commands(source, "add");
+    assert!(!add.contains("players remove"), "{add}");
+  }
+}
@@ -0,0 +1,107 @@
+use crate::{
+  grammar::{
+    builtins::BuiltinType,
+    identifier::{FullIdentifier, Type},
+    parser::ast::TypedName,
+  },
+  ir::{
+    location::DataLocation,
+    register::{Register, REGISTER_COUNT},
+  },
+};
+
+/// How a parameter travels from the caller to the callee
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Passing {
+  Register(Register),
+  Storage(usize),
+}
+
+/// Locations making up the call stack described in design.md
+pub struct CallStack;
+
+impl CallStack {
+  pub fn frames() -> DataLocation {
+    DataLocation::global("frames")
+  }
+
+  pub fn frame() -> DataLocation {
+    DataLocation::global("frames[0]")
+  }
+
+  pub fn local(slot: &str) -> DataLocation {
+    Self::frame().child("locals").child(slot)
+  }
+
+  /// Stack of T registers saved by the caller, one entry per pending call
+  pub fn presaved_stack() -> DataLocation {
+    Self::frame().child("presaved")
+  }
+
+  pub fn presaved_entry() -> DataLocation {
+    Self::frame().child("presaved[0]")
+  }
+
+  /// Where the caller keeps its T registers during a call
+  pub fn presaved(reg: Register) -> DataLocation {
+    Self::presaved_entry().child(&reg.to_string())
+  }
+
+  /// Where the callee keeps the caller's S registers during its execution
+  pub fn postsaved(reg: Register) -> DataLocation {
+    Self::frame().child("postsaved").child(&reg.to_string())
+  }
+
+  pub fn call_storages() -> DataLocation {
+    DataLocation::global("params")
+  }
+
+  pub fn call_storage() -> DataLocation {
+    DataLocation::global("params[0]")
+  }
+
+  pub fn parameter(index: usize) -> DataLocation {
+    Self::call_storage().child(&format!("p{index}"))
+  }
+
+  /// Where the callee leaves a return value that does not fit in a register
+  pub fn return_value() -> DataLocation {
+    Self::call_storage().child("ret")
+  }
+
+  /// Scratch space for intermediate values that do not fit in a register
+  pub fn temporary(index: usize) -> DataLocation {
+    Self::frame().child("temps").child(&format!("t{index}"))
+  }
+
+  /// Frame pushed by a function on entry
+  pub fn new_frame(fname: &str, source: &str) -> String {
+    format!("{{locals:{{}},presaved:[],fname:\"{fname}\",source:\"{source}\"}}")
+  }
+}
+
+/// Numbers and booleans fit in a register, everything else lives in storage
+pub fn is_scalar(typ: &Type<FullIdentifier>) -> bool {
+  matches!(
+    typ,
+    Type::Builtin(BuiltinType::Int | BuiltinType::Float | BuiltinType::Bool)
+  )
+}
+
+/// The first number or boolean parameters are passed through T registers,
+/// everything else goes to the call storage in order of declaration
+pub fn parameter_passing(params: &[TypedName<FullIdentifier>]) -> Vec<Passing> {
+  let mut registers = 0;
+  params
+    .iter()
+    .enumerate()
+    .map(|(index, param)| {
+      if is_scalar(&param.typ) && registers < REGISTER_COUNT {
+        registers += 1;
+        Passing::Register(Register::T(registers - 1))
+      } else {
+        Passing::Storage(index)
+      }
+    })
+    .collect()
+}
@@ -0,0 +1,39 @@
+use crate::{
+  ir::{function::FunctionId, instruction::Instruction},
+  output::OutputDirectory,
+  report::message::Message,
+};
+use std::{cell::RefCell, rc::Rc};
+
+#[derive(Debug, Clone)]
+pub struct McFunction {
+  pub id: FunctionId,
+  pub instructions: Vec<Instruction>,
+}
+
+impl McFunction {
+  pub fn new(id: FunctionId) -> Self {
+    Self {
+      id,
+      instructions: Vec::new(),
+    }
+  }
+
+  pub fn write(&self, function_dir: Rc<RefCell<dyn OutputDirectory>>) {
+    let (name, parents) = self.id.path.split_last().unwrap_or_else(|| {
+      Message::compiler_bug("Tried to write a function with an empty path")
+        .report_and_exit(1)
+    });
+    let dir = parents.iter().fold(function_dir, |dir, part| {
+      let subdir = dir.borrow_mut().subdirectory(part);
+      subdir
+    });
+    let file = dir.borrow_mut().file(&format!("{name}.mcfunction"));
+    let content: String = self
+      .instructions
+      .iter()
+      .map(|instruction| format!("{instruction}\n"))
+      .collect();
+    file.borrow_mut().write(content.as_bytes());
+  }
+}
@@ -0,0 +1,139 @@
+use super::function::McFunction;
+use crate::ir::{
+  function::FunctionId, instruction::Instruction, register::Register,
+  value::Value,
+};
+
+/// Functions shared by all the code of a datapack, implementing operations
+/// scoreboards lack. They are only written to the output when used.
+///
+/// A helper is called like a function taking its operands in `T0` and `T1`
+/// and returning an `int`. It only uses T registers, so it needs no frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Helper {
+  BitAnd,
+  BitOr,
+  BitXor,
+  ShiftLeft,
+  ShiftRight,
+}
+
+const LHS: Register = Register::T(0);
+const RHS: Register = Register::T(1);
+
+impl Helper {
+  pub fn id(&self, namespace: &str) -> FunctionId {
+    let name = match self {
+      Helper::BitAnd => "and",
+      Helper::BitOr => "or",
+      Helper::BitXor => "xor",
+      Helper::ShiftLeft => "shl",
+      Helper::ShiftRight => "shr",
+    };
+    FunctionId::internal(namespace, name)
+  }
+
+  /// The helper function, followed by the functions it calls
+  pub fn functions(&self, namespace: &str) -> Vec<McFunction> {
+    match self {
+      Helper::BitAnd | Helper::BitOr | Helper::BitXor => {
+        self.bitwise(namespace)
+      }
+      Helper::ShiftLeft | Helper::ShiftRight => self.shift(namespace),
+    }
+  }
+
+  /// Goes through the bits of both operands from the lowest one, adding the
+  /// value of the bit to the result when the operation gives 1 for it.
+  /// Scoreboard divisions round towards negative infinity, so halving a
+  /// negative number shifts in ones like an arithmetic shift, and the value
+  /// of the bit wraps around to the sign bit once doubled 31 times.
+  fn bitwise(&self, namespace: &str) -> Vec<McFunction> {
+    let (result, bit, sum, two) = (
+      Register::T(2),
+      Register::T(3),
+      Register::T(4),
+      Register::T(5),
+    );
+    let id = self.id(namespace);
+    let mut entry = McFunction::new(id.clone());
+    let mut step = McFunction::new(id.block("bit"));
+    entry.instructions = vec![
+      Instruction::Set(result, 0),
+      Instruction::Set(bit, 1),
+      Instruction::Set(two, 2),
+      Instruction::ReturnRun(Box::new(call(step.id.clone()))),
+    ];
+
+    // `x - 2 * (x / 2)` is the lowest bit of `x`, so the sum of the lowest
+    // bits of both operands is built without any more registers
+    step.instructions.push(Instruction::Set(sum, 0));
+    for operand in [LHS, RHS] {
+      step.instructions.extend([
+        Instruction::Add(sum, operand),
+        Instruction::Div(operand, two),
+        Instruction::Sub(sum, operand),
+        Instruction::Sub(sum, operand),
+      ]);
+    }
+    let add = Box::new(Instruction::Add(result, bit));
+    step.instructions.extend([
+      match self {
+        Helper::BitAnd => Instruction::If(sum, 2, add),
+        Helper::BitOr => Instruction::Unless(sum, 0, add),
+        _ => Instruction::If(sum, 1, add),
+      },
+      Instruction::Add(bit, bit),
+      Instruction::If(
+        bit,
+        0,
+        Box::new(Instruction::Return(Value::Register(result))),
+      ),
+      Instruction::ReturnRun(Box::new(call(step.id.clone()))),
+    ]);
+    vec![entry, step]
+  }
+
+  /// Doubles or halves the left operand as many times as the right one says,
+  /// taken modulo 32
+  fn shift(&self, namespace: &str) -> Vec<McFunction> {
+    let constant = Register::T(2);
+    let id = self.id(namespace);
+    let mut entry = McFunction::new(id.clone());
+    let mut step = McFunction::new(id.block("step"));
+    let left = *self == Helper::ShiftLeft;
+    entry.instructions = vec![
+      Instruction::Set(constant, 32),
+      Instruction::Mod(RHS, constant),
+    ];
+    if !left {
+      entry.instructions.push(Instruction::Set(constant, 2));
+    }
+    entry
+      .instructions
+      .push(Instruction::ReturnRun(Box::new(call(step.id.clone()))));
+    step.instructions = vec![
+      Instruction::If(
+        RHS,
+        0,
+        Box::new(Instruction::Return(Value::Register(LHS))),
+      ),
+      if left {
+        Instruction::Add(LHS, LHS)
+      } else {
+        Instruction::Div(LHS, constant)
+      },
+      Instruction::AddConstant(RHS, -1),
+      Instruction::ReturnRun(Box::new(call(step.id.clone()))),
+    ];
+    vec![entry, step]
+  }
+}
+
+fn call(function: FunctionId) -> Instruction {
+  Instruction::Call {
+    function,
+    context: None,
+    result: None,
+  }
+}
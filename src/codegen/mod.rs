@@ -0,0 +1,144 @@
+pub mod allocator;
+pub mod builder;
+pub mod frame;
+pub mod function;
+pub mod helper;
+
+use crate::{
+  grammar::{
+    identifier::{FullIdentifier, Name, Type},
+    operators::AssignOp,
+    parser::ast::Expression,
+    semifier::{
+      ast::{FnDecl, FnStatement},
+      module::ModulePath,
+      program::Program,
+    },
+  },
+  ir::{
+    function::FunctionId, instruction::Instruction, location::DataLocation,
+  },
+};
+use builder::FunctionBuilder;
+use frame::CallStack;
+use function::McFunction;
+use helper::Helper;
+use std::{cell::RefCell, collections::BTreeSet};
+
+pub struct CodeGenerator {
+  namespace: String,
+  // Fixed-point value of `1.0`, see FloatConfig
+  float_scale: i32,
+  // Helpers called by the code generated so far
+  helpers: RefCell<BTreeSet<Helper>>,
+}
+
+impl CodeGenerator {
+  pub fn new(namespace: &str, float_scale: i32) -> Self {
+    Self {
+      namespace: namespace.to_string(),
+      float_scale,
+      helpers: RefCell::new(BTreeSet::new()),
+    }
+  }
+
+  pub fn generate(&self, program: &Program) -> Vec<McFunction> {
+    let mut functions = self.generate_load(program);
+    functions.extend(
+      program
+        .functions()
+        .flat_map(|(id, decl)| self.generate_function(program, id, decl)),
+    );
+    for helper in self.helpers.borrow().iter() {
+      functions.extend(helper.functions(&self.namespace));
+    }
+    functions
+  }
+
+  /// Id of a helper function, which is then written to the output
+  pub fn helper(&self, helper: Helper) -> FunctionId {
+    self.helpers.borrow_mut().insert(helper);
+    helper.id(&self.namespace)
+  }
+
+  /// Function run by the `#minecraft:load` tag
+  pub fn load_id(&self) -> FunctionId {
+    FunctionId::internal(&self.namespace, "load")
+  }
+
+  pub fn global_location(&self, path: &[Name]) -> DataLocation {
+    DataLocation::local(&self.namespace, &format!("globals.{}", path.join("-")))
+  }
+
+  /// Builds the load function, followed by the function initializing the
+  /// globals whose value is not known at compile time if any
+  fn generate_load(&self, program: &Program) -> Vec<McFunction> {
+    let mut load = McFunction::new(self.load_id());
+    load.instructions.push(Instruction::CreateObjective);
+    // Frames left behind by a function aborted mid-execution are useless
+    load.instructions.extend([
+      Instruction::DataSet(CallStack::frames(), String::from("[]")),
+      Instruction::DataSet(CallStack::call_storages(), String::from("[]")),
+    ]);
+
+    // Constant globals are set directly. The others are assigned by a
+    // function, in order, once every constant one is set since they cannot
+    // be read by a constant initializer.
+    let mut assignments = Vec::new();
+    for (id, global) in program.initialization_order() {
+      let FullIdentifier::Global(path) = id else {
+        continue;
+      };
+      if global.constant {
+        continue;
+      }
+      match &global.value {
+        Some(value) => load.instructions.push(Instruction::DataSet(
+          self.global_location(path),
+          builder::snbt(value),
+        )),
+        None => assignments.push(FnStatement::Assignment {
+          target: Expression::AtomIdentifier(
+            global.name.clone().map(|_| id.clone()),
+          ),
+          op: global.name.clone().map(|_| AssignOp::Identity),
+          val: global.val.clone(),
+        }),
+      }
+    }
+    let Some(FnStatement::Assignment { target, .. }) = assignments.first()
+    else {
+      return vec![load];
+    };
+    let init = FnDecl {
+      module: ModulePath::main(),
+      attributes: Vec::new(),
+      name: target.span().map(|_| Name::from("init")),
+      params: Vec::new(),
+      ret_type: Type::void(),
+      ret_span: None,
+      dependencies: Vec::new(),
+      locals: Vec::new(),
+      body: assignments,
+    };
+    let id = FunctionId::internal(&self.namespace, "init");
+    load.instructions.push(Instruction::Call {
+      function: id.clone(),
+      context: None,
+      result: None,
+    });
+    let mut functions = vec![load];
+    functions.extend(FunctionBuilder::new(self, program, id).build(&init));
+    functions
+  }
+
+  fn generate_function(
+    &self,
+    program: &Program,
+    id: &FullIdentifier,
+    decl: &FnDecl,
+  ) -> Vec<McFunction> {
+    let id = FunctionId::from_identifier(&self.namespace, id);
+    FunctionBuilder::new(self, program, id).build(decl)
+  }
+}
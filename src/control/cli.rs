@@ -1,5 +1,5 @@
 use clap::{Parser, ValueEnum};
-use std::path::PathBuf;
+use std::{fmt, path::PathBuf};
 
 #[derive(Debug, Clone, ValueEnum)]
 pub enum OutputType {
@@ -7,11 +7,11 @@ pub enum OutputType {
   Zip,
 }
 
-impl ToString for OutputType {
-  fn to_string(&self) -> String {
+impl fmt::Display for OutputType {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
     match self {
-      OutputType::Directory => String::from("directory"),
-      OutputType::Zip => String::from("zip"),
+      OutputType::Directory => write!(f, "directory"),
+      OutputType::Zip => write!(f, "zip"),
     }
   }
 }
@@ -27,9 +27,17 @@ pub struct Args {
   pub output: PathBuf,
   #[arg(short = 't', default_value_t = OutputType::Directory)]
   pub output_type: OutputType,
-  /// Overwrite existing files/folders
+  /// Overwrite an existing datapack. Where it cannot be swapped with the new
+  /// one at once, it is moved aside to `.<name>.old` until replaced
   #[arg(short = 'f', default_value_t = false)]
   pub force: bool,
+  /// Overwrite existing files/folders, even when they are not datapacks,
+  /// replacing them like -f does
+  #[arg(short = 'F', default_value_t = false)]
+  pub force_any: bool,
+  /// Warn about functions unreachable from exported functions
+  #[arg(short = 'w', default_value_t = false)]
+  pub warn_unused: bool,
 }
 
 pub fn getargs() -> Args {
@@ -2,11 +2,16 @@ use std::{fs, path::PathBuf};
 
 use serde::Deserialize;
 
-use crate::report::message::{Message, MessageType};
+use crate::{
+  format::version::Target,
+  report::message::{Message, MessageType},
+};
 
 #[derive(Debug, Deserialize)]
 pub struct Config {
   pub datapack: DatapackConfig,
+  #[serde(default)]
+  pub float: FloatConfig,
 }
 
 #[derive(Debug, Deserialize)]
@@ -19,16 +24,47 @@ pub struct DatapackConfig {
   pub format: usize,
   /// Datapack description
   pub description: String,
+  /// Minecraft release, or oldest and newest ones, the datapack is made for
+  #[serde(default)]
+  pub minecraft: Target,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FloatConfig {
+  /// Fixed-point value of `1.0`: floats are stored as integers counting
+  /// `1 / scale` units
+  pub scale: u32,
+}
+
+/// Largest scale whose remainders multiply without overflowing a scoreboard,
+/// see the fixed-point multiplication in design.md
+pub const MAX_FLOAT_SCALE: u32 = 46340;
+
+impl Default for FloatConfig {
+  fn default() -> Self {
+    Self { scale: 1000 }
+  }
 }
 
 pub fn getconfig(path: PathBuf) -> Config {
   let config_raw = fs::read_to_string(path.clone())
     .unwrap_or_else(|err| Message::input_error(err, &path).report_and_exit(1));
-  toml::from_str(&config_raw).unwrap_or_else(|err| {
+  let config: Config = toml::from_str(&config_raw).unwrap_or_else(|err| {
+    Message::new(
+      &format!("Could not parse `{path:?}`: {}", err),
+      MessageType::Error,
+    )
+    .report_and_exit(1)
+  });
+  if !(1..=MAX_FLOAT_SCALE).contains(&config.float.scale) {
     Message::new(
-      &format!("Could not parse `{path:?}`: {}", err.to_string()),
+      &format!("Invalid float scale {} in `{path:?}`", config.float.scale),
       MessageType::Error,
     )
+    .with_note(&format!(
+      "The scale must be between 1 and {MAX_FLOAT_SCALE}"
+    ))
     .report_and_exit(1)
-  })
+  }
+  config
 }
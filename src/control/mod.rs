@@ -1,2 +1,2 @@
 pub mod cli;
-pub mod config;
\ No newline at end of file
+pub mod config;
@@ -1 +1,3 @@
-pub mod pack;
\ No newline at end of file
+pub mod pack;
+pub mod tag;
+pub mod version;
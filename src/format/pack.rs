@@ -1,16 +1,125 @@
+use super::version::Target;
 use serde::Serialize;
 
+/// Content of `pack.mcmeta`
 #[derive(Debug, Serialize)]
 pub struct PackMeta {
+  pack: Pack,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  overlays: Option<Overlays>,
+}
+
+#[derive(Debug, Serialize)]
+struct Pack {
+  pack_format: u32,
   description: String,
-  format: usize,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  supported_formats: Option<FormatRange>,
+}
+
+/// Directories whose files replace the ones of the datapack for some formats
+#[derive(Debug, Serialize)]
+struct Overlays {
+  entries: Vec<Overlay>,
+}
+
+#[derive(Debug, Serialize)]
+struct Overlay {
+  formats: FormatRange,
+  directory: String,
+}
+
+#[derive(Debug, Serialize)]
+struct FormatRange {
+  min_inclusive: u32,
+  max_inclusive: u32,
 }
 
 impl PackMeta {
-  pub fn new(description: &str) -> Self {
+  pub fn new(description: &str, target: &Target) -> Self {
+    let entries: Vec<_> = target
+      .variants()
+      .iter()
+      .skip(1)
+      .map(|variant| Overlay {
+        formats: FormatRange::new(variant.formats),
+        directory: variant.overlay_directory(),
+      })
+      .collect();
+
+    Self {
+      pack: Pack {
+        pack_format: target.oldest.pack_format,
+        description: description.to_string(),
+        supported_formats: target.supported_formats().map(FormatRange::new),
+      },
+      overlays: (!entries.is_empty()).then_some(Overlays { entries }),
+    }
+  }
+}
+
+impl FormatRange {
+  fn new((min, max): (u32, u32)) -> Self {
     Self {
-      description: description.to_string(),
-      format: 48,
+      min_inclusive: min,
+      max_inclusive: max,
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::PackMeta;
+  use crate::format::version::{Release, Target};
+  use serde_json::json;
+
+  fn pack_meta(oldest: &str, newest: &str) -> serde_json::Value {
+    let target = Target {
+      oldest: Release::find(oldest).unwrap(),
+      newest: Release::find(newest).unwrap(),
+    };
+    serde_json::to_value(PackMeta::new("A pack", &target)).unwrap()
+  }
+
+  #[test]
+  fn single_format() {
+    assert_eq!(
+      pack_meta("1.21", "1.21.1"),
+      json!({ "pack": { "pack_format": 48, "description": "A pack" } })
+    );
+  }
+
+  #[test]
+  fn supported_formats() {
+    assert_eq!(
+      pack_meta("1.21", "1.21.4"),
+      json!({
+        "pack": {
+          "pack_format": 48,
+          "description": "A pack",
+          "supported_formats": { "min_inclusive": 48, "max_inclusive": 61 },
+        }
+      })
+    );
+  }
+
+  #[test]
+  fn overlays() {
+    assert_eq!(
+      pack_meta("1.20.2", "1.21"),
+      json!({
+        "pack": {
+          "pack_format": 18,
+          "description": "A pack",
+          "supported_formats": { "min_inclusive": 18, "max_inclusive": 48 },
+        },
+        "overlays": {
+          "entries": [{
+            "formats": { "min_inclusive": 45, "max_inclusive": 48 },
+            "directory": "lc_1_21",
+          }],
+        },
+      })
+    );
+  }
+}
@@ -0,0 +1,14 @@
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct FunctionTag {
+  values: Vec<String>,
+}
+
+impl FunctionTag {
+  pub fn new(values: &[String]) -> Self {
+    Self {
+      values: values.to_vec(),
+    }
+  }
+}
@@ -0,0 +1,256 @@
+use serde::Deserialize;
+use std::fmt;
+
+/// A Minecraft release a datapack can be loaded by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Release {
+  pub name: &'static str,
+  pub pack_format: u32,
+}
+
+/// Every release the generated commands work on, oldest first. Macros and
+/// `return run` appeared in 1.20.2.
+pub const RELEASES: [Release; 14] = [
+  Release::new("1.20.2", 18),
+  Release::new("1.20.3", 26),
+  Release::new("1.20.4", 26),
+  Release::new("1.20.5", 41),
+  Release::new("1.20.6", 41),
+  Release::new("1.21", 48),
+  Release::new("1.21.1", 48),
+  Release::new("1.21.2", 57),
+  Release::new("1.21.3", 57),
+  Release::new("1.21.4", 61),
+  Release::new("1.21.5", 71),
+  Release::new("1.21.6", 80),
+  Release::new("1.21.7", 81),
+  Release::new("1.21.8", 81),
+];
+
+/// First pack format where the directories holding functions and function
+/// tags are named `function` rather than `functions`
+pub const SINGULAR_DIRECTORIES_FORMAT: u32 = 45;
+
+/// Formats from which the datapack is written differently than for the
+/// previous ones
+const CHANGES: [u32; 1] = [SINGULAR_DIRECTORIES_FORMAT];
+
+impl Release {
+  const fn new(name: &'static str, pack_format: u32) -> Self {
+    Self { name, pack_format }
+  }
+
+  pub fn find(name: &str) -> Option<Self> {
+    let name = name.strip_suffix(".0").unwrap_or(name);
+    RELEASES.into_iter().find(|release| release.name == name)
+  }
+
+  /// Name of the directories holding functions and function tags
+  pub fn function_directory(&self) -> &'static str {
+    if self.pack_format < SINGULAR_DIRECTORIES_FORMAT {
+      "functions"
+    } else {
+      "function"
+    }
+  }
+}
+
+/// Releases a datapack is made for, from `oldest` to `newest` included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "TargetConfig")]
+pub struct Target {
+  pub oldest: Release,
+  pub newest: Release,
+}
+
+/// A target as written in `lc.toml`: a single release, or the oldest and
+/// newest ones
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum TargetConfig {
+  Release(String),
+  Range(String, String),
+}
+
+impl Target {
+  /// Formats the datapack declares it supports, when there are several
+  pub fn supported_formats(&self) -> Option<(u32, u32)> {
+    (self.oldest.pack_format != self.newest.pack_format)
+      .then_some((self.oldest.pack_format, self.newest.pack_format))
+  }
+
+  /// The formats of the target split where the datapack is written
+  /// differently, oldest first. The first variant is the base of the
+  /// datapack, and each other one is an overlay.
+  pub fn variants(&self) -> Vec<Variant> {
+    let (oldest, newest) = (self.oldest.pack_format, self.newest.pack_format);
+    let mut starts = vec![oldest];
+    starts.extend(
+      CHANGES
+        .into_iter()
+        .filter(|format| (oldest + 1..=newest).contains(format)),
+    );
+    let ends = starts.iter().skip(1).map(|start| start - 1).chain([newest]);
+    starts
+      .iter()
+      .zip(ends)
+      .map(|(&start, end)| Variant {
+        release: RELEASES
+          .into_iter()
+          .find(|release| release.pack_format >= start)
+          .unwrap(),
+        formats: (start, end),
+      })
+      .collect()
+  }
+}
+
+/// Formats of a target the datapack is written the same way for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Variant {
+  /// Oldest release of the variant, deciding how its files are written
+  pub release: Release,
+  /// Smallest and largest pack formats the variant is used for
+  pub formats: (u32, u32),
+}
+
+impl Variant {
+  /// Name of the directory holding the variant when it is an overlay
+  pub fn overlay_directory(&self) -> String {
+    format!("lc_{}", self.release.name.replace('.', "_"))
+  }
+}
+
+impl Default for Target {
+  fn default() -> Self {
+    let release = Release::find("1.21").unwrap();
+    Self {
+      oldest: release,
+      newest: release,
+    }
+  }
+}
+
+impl TryFrom<TargetConfig> for Target {
+  type Error = String;
+
+  fn try_from(config: TargetConfig) -> Result<Self, Self::Error> {
+    let (oldest, newest) = match &config {
+      TargetConfig::Release(release) => (release, release),
+      TargetConfig::Range(oldest, newest) => (oldest, newest),
+    };
+    let find = |name: &str| {
+      Release::find(name).ok_or_else(|| {
+        format!(
+          "unsupported Minecraft version `{name}`, expected one from {} to {}",
+          RELEASES[0],
+          RELEASES[RELEASES.len() - 1]
+        )
+      })
+    };
+    let target = Self {
+      oldest: find(oldest)?,
+      newest: find(newest)?,
+    };
+
+    if target.oldest.pack_format > target.newest.pack_format {
+      return Err(format!(
+        "Minecraft version `{}` is newer than `{}`",
+        target.oldest, target.newest
+      ));
+    }
+    Ok(target)
+  }
+}
+
+impl fmt::Display for Release {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", self.name)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Release, Target, TargetConfig};
+
+  fn target(config: TargetConfig) -> Result<Target, String> {
+    Target::try_from(config)
+  }
+
+  #[test]
+  fn single_release() {
+    let target = target(TargetConfig::Release(String::from("1.21.0"))).unwrap();
+    assert_eq!(target.oldest, Release::find("1.21").unwrap());
+    assert_eq!(target.newest, target.oldest);
+    assert_eq!(target.supported_formats(), None);
+  }
+
+  #[test]
+  fn range_of_releases() {
+    let target = target(TargetConfig::Range(
+      String::from("1.20.4"),
+      String::from("1.21.1"),
+    ))
+    .unwrap();
+    assert_eq!(target.supported_formats(), Some((26, 48)));
+  }
+
+  #[test]
+  fn range_within_one_format() {
+    let target = target(TargetConfig::Range(
+      String::from("1.21"),
+      String::from("1.21.1"),
+    ))
+    .unwrap();
+    assert_eq!(target.supported_formats(), None);
+  }
+
+  #[test]
+  fn unsupported_release() {
+    let err = target(TargetConfig::Release(String::from("1.19"))).unwrap_err();
+    assert_eq!(
+      err,
+      "unsupported Minecraft version `1.19`, expected one from 1.20.2 to 1.21.8"
+    );
+  }
+
+  #[test]
+  fn reversed_range() {
+    let err = target(TargetConfig::Range(
+      String::from("1.21"),
+      String::from("1.20.2"),
+    ))
+    .unwrap_err();
+    assert_eq!(err, "Minecraft version `1.21` is newer than `1.20.2`");
+  }
+
+  #[test]
+  fn target_within_one_layout_has_no_overlay() {
+    let target = target(TargetConfig::Range(
+      String::from("1.21"),
+      String::from("1.21.8"),
+    ))
+    .unwrap();
+    let variants = target.variants();
+    assert_eq!(variants.len(), 1);
+    assert_eq!(variants[0].release, target.oldest);
+    assert_eq!(variants[0].formats, (48, 81));
+  }
+
+  #[test]
+  fn target_across_layouts_is_split() {
+    let target = target(TargetConfig::Range(
+      String::from("1.20.2"),
+      String::from("1.21"),
+    ))
+    .unwrap();
+    let variants = target.variants();
+    assert_eq!(variants.len(), 2);
+    assert_eq!(variants[0].release.name, "1.20.2");
+    assert_eq!(variants[0].formats, (18, 44));
+    // The first release from the format where the layout changes
+    assert_eq!(variants[1].release.name, "1.21");
+    assert_eq!(variants[1].formats, (45, 48));
+    assert_eq!(variants[1].overlay_directory(), "lc_1_21");
+  }
+}
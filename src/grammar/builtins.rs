@@ -7,6 +7,10 @@ pub enum Builtin {
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum BuiltinFn {
   PrintLn,
+  Len,
+  Push,
+  Pop,
+  Substring,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -17,4 +21,94 @@ pub enum BuiltinType {
   Float,
   Char,
   String,
-}
\ No newline at end of file
+}
+
+impl BuiltinFn {
+  pub fn name(&self) -> &'static str {
+    match self {
+      BuiltinFn::PrintLn => "println",
+      BuiltinFn::Len => "len",
+      BuiltinFn::Push => "push",
+      BuiltinFn::Pop => "pop",
+      BuiltinFn::Substring => "substring",
+    }
+  }
+
+  /// Number of arguments taken, if fixed
+  pub fn arity(&self) -> Option<usize> {
+    match self {
+      BuiltinFn::PrintLn => None,
+      BuiltinFn::Len | BuiltinFn::Pop => Some(1),
+      BuiltinFn::Push => Some(2),
+      BuiltinFn::Substring => Some(3),
+    }
+  }
+
+  /// Signature of the builtin, as shown in diagnostics
+  pub fn signature(&self) -> &'static str {
+    match self {
+      BuiltinFn::PrintLn => "fn println(format: string, ...)",
+      BuiltinFn::Len => "fn len(value: [T] | string) -> int",
+      BuiltinFn::Push => "fn push(array: [T], value: T)",
+      BuiltinFn::Pop => "fn pop(array: [T]) -> T",
+      BuiltinFn::Substring => {
+        "fn substring(s: string, start: int, end: int) -> string"
+      }
+    }
+  }
+
+  /// Whether the builtin modifies the variable given as first argument
+  pub fn mutates(&self) -> bool {
+    matches!(self, BuiltinFn::Push | BuiltinFn::Pop)
+  }
+}
+
+impl BuiltinType {
+  pub fn name(&self) -> &'static str {
+    match self {
+      BuiltinType::Void => "void",
+      BuiltinType::Bool => "bool",
+      BuiltinType::Int => "int",
+      BuiltinType::Float => "float",
+      BuiltinType::Char => "char",
+      BuiltinType::String => "string",
+    }
+  }
+}
+
+/// Piece of a `println` format string
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatPart {
+  Text(String),
+  // `{}`, standing for the next argument
+  Placeholder,
+}
+
+/// Splits a format string into text and placeholders, with `{{` and `}}`
+/// standing for single braces. Fails with the first unmatched brace
+pub fn parse_format(format: &str) -> Result<Vec<FormatPart>, char> {
+  let mut parts = Vec::new();
+  let mut text = String::new();
+  let mut chars = format.chars().peekable();
+  while let Some(c) = chars.next() {
+    match (c, chars.peek()) {
+      ('{', Some('}')) => {
+        chars.next();
+        if !text.is_empty() {
+          parts.push(FormatPart::Text(std::mem::take(&mut text)));
+        }
+        parts.push(FormatPart::Placeholder);
+      }
+      ('{', Some('{')) | ('}', Some('}')) => {
+        chars.next();
+        text.push(c);
+      }
+      ('{' | '}', _) => return Err(c),
+      _ => text.push(c),
+    }
+  }
+  if !text.is_empty() {
+    parts.push(FormatPart::Text(text));
+  }
+  Ok(parts)
+}
@@ -1,8 +1,11 @@
 use crate::report::{location::WithLineInfo, message::Message};
+use std::fmt;
 
 use super::{
   builtins::{BuiltinFn, BuiltinType},
-  semifier::module::ModulePath,
+  semifier::{
+    error::SemanticError, module::ModulePath, resolver::NameResolver,
+  },
 };
 
 pub type Name = String;
@@ -13,16 +16,17 @@ pub struct Identifier {
   pub parts: Vec<WithLineInfo<Name>>,
 }
 
-#[derive(Debug, Clone, PartialEq, Hash, Eq)]
+#[derive(Debug, Clone, PartialEq, Hash, Eq, PartialOrd, Ord)]
 pub enum FullIdentifier {
   Local(Name),
   Global(Vec<Name>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Type {
+pub enum Type<I: Clone = Identifier> {
   Builtin(BuiltinType),
-  Declared(Identifier),
+  Declared(I),
+  Array(Box<Type<I>>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -32,6 +36,7 @@ pub enum CallTarget<I: Clone> {
 }
 
 impl Identifier {
+  #[allow(dead_code)]
   pub fn from_name(name: WithLineInfo<Name>) -> Self {
     Identifier {
       root: false,
@@ -46,14 +51,33 @@ impl Identifier {
     self.parts.last().unwrap().value.clone()
   }
 
+  #[allow(dead_code)]
   pub fn name_line_info(&self) -> WithLineInfo<Name> {
     self.parts.last().unwrap().clone()
   }
 
+  #[allow(dead_code)]
   pub fn line_info(&self) -> WithLineInfo<Name> {
     self.parts.first().unwrap().clone()
   }
 
+  /// Line information spanning all parts of the identifier
+  pub fn span<T>(&self, value: T) -> WithLineInfo<T> {
+    let first = self.parts.first().unwrap();
+    let last = self.parts.last().unwrap();
+    let len = if first.line == last.line {
+      last.column + last.len - first.column
+    } else {
+      first.len
+    };
+    WithLineInfo {
+      value,
+      line: first.line,
+      column: first.column,
+      len,
+    }
+  }
+
   pub fn full_path(&self, current_path: ModulePath) -> FullIdentifier {
     if self.root {
       FullIdentifier::Global(
@@ -74,6 +98,7 @@ impl FullIdentifier {
     FullIdentifier::Global(path)
   }
 
+  #[allow(dead_code)]
   pub fn local(&self) -> bool {
     matches!(self, FullIdentifier::Local(_))
   }
@@ -101,3 +126,52 @@ impl FullIdentifier {
     }
   }
 }
+
+impl Type {
+  pub fn resolve(
+    self,
+    resolver: &NameResolver,
+  ) -> Result<Type<FullIdentifier>, Box<SemanticError>> {
+    Ok(match self {
+      Type::Builtin(builtin) => Type::Builtin(builtin),
+      Type::Declared(id) => Type::Declared(resolver.resolve_type(&id)?),
+      Type::Array(element) => Type::Array(Box::new(element.resolve(resolver)?)),
+    })
+  }
+}
+
+impl Type<FullIdentifier> {
+  pub fn void() -> Self {
+    Type::Builtin(BuiltinType::Void)
+  }
+
+  pub fn string() -> Self {
+    Type::Builtin(BuiltinType::String)
+  }
+
+  pub fn is_numeric(&self) -> bool {
+    matches!(
+      self,
+      Type::Builtin(BuiltinType::Int) | Type::Builtin(BuiltinType::Float)
+    )
+  }
+}
+
+impl fmt::Display for Type<FullIdentifier> {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Type::Builtin(builtin) => write!(f, "{}", builtin.name()),
+      Type::Declared(id) => write!(f, "{id}"),
+      Type::Array(element) => write!(f, "[{element}]"),
+    }
+  }
+}
+
+impl fmt::Display for FullIdentifier {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      FullIdentifier::Local(name) => write!(f, "{name}"),
+      FullIdentifier::Global(path) => write!(f, "{}", path.join("::")),
+    }
+  }
+}
@@ -4,11 +4,19 @@ pub enum Keyword {
   Use,
   Let,
   Mut,
+  Const,
   Fn,
   If,
   Else,
+  While,
+  Loop,
+  For,
+  In,
+  Break,
+  Continue,
   Ret,
   Struct,
+  As,
 }
 
 impl Keyword {
@@ -18,11 +26,19 @@ impl Keyword {
       Keyword::Use => "use",
       Keyword::Let => "let",
       Keyword::Mut => "mut",
+      Keyword::Const => "const",
       Keyword::Fn => "fn",
       Keyword::If => "if",
       Keyword::Else => "else",
+      Keyword::While => "while",
+      Keyword::Loop => "loop",
+      Keyword::For => "for",
+      Keyword::In => "in",
+      Keyword::Break => "break",
+      Keyword::Continue => "continue",
       Keyword::Ret => "return",
       Keyword::Struct => "struct",
+      Keyword::As => "as",
     }
   }
 }
@@ -3,7 +3,7 @@ use crate::report::message::{
   line::{HighlightedLine, LineType},
   Message, MessageMeta, MessageType,
 };
-use std::path::PathBuf;
+use std::path::Path;
 
 #[derive(Debug, Clone)]
 pub struct LexerError {
@@ -13,10 +13,10 @@ pub struct LexerError {
 }
 
 impl LexerError {
-  pub fn get_report(&self, path: &PathBuf, source: &str) -> Message {
+  pub fn get_report(&self, path: &Path, source: &str) -> Message {
     let line = source.lines().nth(self.line - 1).unwrap();
 
-    let line = HighlightedLine::new(self.line, &line, LineType::Source)
+    let line = HighlightedLine::new(self.line, line, LineType::Source)
       .with_highlight(
         Highlight::new(self.column, self.len, HighlightType::Focus)
           .with_label("here"),
@@ -24,7 +24,7 @@ impl LexerError {
 
     Message::new("Unexpected token", MessageType::Error)
       .with_meta(MessageMeta::FileLocation(
-        path.clone(),
+        path.to_path_buf(),
         self.line,
         self.column,
       ))
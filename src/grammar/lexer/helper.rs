@@ -14,7 +14,7 @@ pub fn parse_literal_integer(
   base: u32,
 ) -> Result<u64, &'static str> {
   let literal = literal.replace("_", "");
-  Ok(u64::from_str_radix(&literal, base).or(Err(""))?)
+  u64::from_str_radix(&literal, base).or(Err(""))
 }
 pub fn parse_literal_float(literal: &str) -> Result<Token, &'static str> {
   let literal = literal.replace("_", "");
@@ -26,11 +26,7 @@ pub fn parse_identifier(
 ) -> Result<Token, &'static str> {
   let kw = parts.iter().find_map(|part| {
     let kws = keywords();
-    if let Some(token) = kws.get(part.value.as_str()) {
-      Some(token.clone())
-    } else {
-      None
-    }
+    kws.get(part.value.as_str()).cloned()
   });
   if let Some(token) = kw {
     if root || parts.len() != 1 {
@@ -56,11 +52,19 @@ pub fn keywords() -> &'static HashMap<&'static str, Token> {
     map.insert("fn", Token::Keyword(Keyword::Fn));
     map.insert("let", Token::Keyword(Keyword::Let));
     map.insert("mut", Token::Keyword(Keyword::Mut));
+    map.insert("const", Token::Keyword(Keyword::Const));
     map.insert("if", Token::Keyword(Keyword::If));
     map.insert("else", Token::Keyword(Keyword::Else));
+    map.insert("while", Token::Keyword(Keyword::While));
+    map.insert("loop", Token::Keyword(Keyword::Loop));
+    map.insert("for", Token::Keyword(Keyword::For));
+    map.insert("in", Token::Keyword(Keyword::In));
+    map.insert("break", Token::Keyword(Keyword::Break));
+    map.insert("continue", Token::Keyword(Keyword::Continue));
     map.insert("return", Token::Keyword(Keyword::Ret));
 
     map.insert("struct", Token::Keyword(Keyword::Struct));
+    map.insert("as", Token::Keyword(Keyword::As));
 
     map.insert("void", Token::Builtin(Builtin::Type(BuiltinType::Void)));
     map.insert("bool", Token::Builtin(Builtin::Type(BuiltinType::Bool)));
@@ -70,6 +74,13 @@ pub fn keywords() -> &'static HashMap<&'static str, Token> {
     map.insert("string", Token::Builtin(Builtin::Type(BuiltinType::String)));
 
     map.insert("println", Token::Builtin(Builtin::Fn(BuiltinFn::PrintLn)));
+    map.insert("len", Token::Builtin(Builtin::Fn(BuiltinFn::Len)));
+    map.insert("push", Token::Builtin(Builtin::Fn(BuiltinFn::Push)));
+    map.insert("pop", Token::Builtin(Builtin::Fn(BuiltinFn::Pop)));
+    map.insert(
+      "substring",
+      Token::Builtin(Builtin::Fn(BuiltinFn::Substring)),
+    );
 
     map
   })
@@ -24,3 +24,41 @@ impl Lexer {
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::{token::Token, Lexer};
+  use crate::grammar::operators::{AssignOp, BinOp, Op, UnOp};
+
+  fn lex(input: &str) -> Vec<Token> {
+    Lexer
+      .lex(input)
+      .unwrap()
+      .into_iter()
+      .map(|token| token.value)
+      .filter(|token| *token != Token::Separator)
+      .collect()
+  }
+
+  #[test]
+  fn not_equals_is_one_operator() {
+    let tokens = lex("a != b");
+    assert_eq!(tokens[1], Token::Op(Op::Bin(BinOp::NotEqual)));
+    assert_eq!(tokens.len(), 3);
+    let tokens = lex("a!=b");
+    assert_eq!(tokens[1], Token::Op(Op::Bin(BinOp::NotEqual)));
+  }
+
+  #[test]
+  fn not_before_operand() {
+    assert_eq!(lex("!a")[0], Token::Op(Op::Un(UnOp::Not)));
+    let not = Token::Op(Op::Un(UnOp::Not));
+    assert_eq!(lex("!!a")[..2], [not.clone(), not]);
+  }
+
+  #[test]
+  fn remainder_assignment_is_one_operator() {
+    assert_eq!(lex("a %= b")[1], Token::AssignOp(AssignOp::Mod));
+    assert_eq!(lex("a % b")[1], Token::Op(Op::Bin(BinOp::Mod)));
+  }
+}
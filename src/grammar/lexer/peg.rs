@@ -36,6 +36,7 @@ peg::parser! {
       bracket_close()
 
     rule semicolon() -> Token = ";" { Token::SemiColon }
+    rule range() -> Token = ".." { Token::Range }
     rule dot() -> Token = "." { Token::Dot }
     rule comma() -> Token = "," { Token::Comma }
     rule colon() -> Token = ":" { Token::Colon }
@@ -57,9 +58,9 @@ rule digit_bin() -> u64 = d:$(['0'..='1']) { d.parse().unwrap() }    rule digit_
       "0x" n:$(digit_hex() (digit_hex() / "_")*) { n.into() }
 
     rule literal_bin() -> u64 =
-      n:(sequence_bin()) {? parse_literal_integer(&n, 02) }
+      n:(sequence_bin()) {? parse_literal_integer(&n, 2) }
     rule literal_oct() -> u64 =
-      n:(sequence_oct()) {? parse_literal_integer(&n, 08) }
+      n:(sequence_oct()) {? parse_literal_integer(&n, 8) }
     rule literal_dec() -> u64 =
       n:(sequence_dec()) {? parse_literal_integer(&n, 10) }
     rule literal_hex() -> u64 =
@@ -84,7 +85,7 @@ rule digit_bin() -> u64 = d:$(['0'..='1']) { d.parse().unwrap() }    rule digit_
     rule string_normal() -> char = [^ '"' | '\\' | '\n' | '\r' | '\t']
 
     rule escape_quote() -> char =
-      "\\" c:$("'" / "\"") { c.chars().nth(0).unwrap() }
+      "\\" c:$("'" / "\"") { c.chars().next().unwrap() }
     rule escape_ascii() -> char =
       "\\x" cx:$(digit_oct() digit_hex()) {
         char::from_u32(u32::from_str_radix(cx, 16).unwrap()).unwrap()
@@ -105,6 +106,7 @@ rule digit_bin() -> u64 = d:$(['0'..='1']) { d.parse().unwrap() }    rule digit_
       "\"" s:(
         (
           string_normal() /
+          escape_quote() /
           escape_ascii() /
           escape_unicode()
         )*
@@ -123,7 +125,8 @@ rule digit_bin() -> u64 = d:$(['0'..='1']) { d.parse().unwrap() }    rule digit_
         parse_identifier(root == "::", parts)
       }
 
-    rule unop_not() -> Token = "!" { Token::Op(Op::Un(UnOp::Not)) }
+    rule unop_not() -> Token = "!" !"=" { Token::Op(Op::Un(UnOp::Not)) }
+    rule unop_bit_not() -> Token = "~" { Token::Op(Op::Un(UnOp::BitNot)) }
 
     rule op_add() -> Token = "+" { Token::Op(Op::RawAdd) }
     rule op_sub() -> Token = "-" { Token::Op(Op::RawSub) }
@@ -139,24 +142,42 @@ rule digit_bin() -> u64 = d:$(['0'..='1']) { d.parse().unwrap() }    rule digit_
       ">=" { Token::Op(Op::Bin(BinOp::GreaterOrEqual)) }
     rule binop_lesser_equals() -> Token =
       "<=" { Token::Op(Op::Bin(BinOp::LessOrEqual)) }
+    rule binop_and() -> Token = "&&" { Token::Op(Op::Bin(BinOp::And)) }
+    rule binop_or() -> Token = "||" { Token::Op(Op::Bin(BinOp::Or)) }
+    rule binop_bit_and() -> Token = "&" { Token::Op(Op::Bin(BinOp::BitAnd)) }
+    rule binop_bit_or() -> Token = "|" { Token::Op(Op::Bin(BinOp::BitOr)) }
+    rule binop_bit_xor() -> Token = "^" { Token::Op(Op::Bin(BinOp::BitXor)) }
+    rule binop_shift_left() -> Token =
+      "<<" { Token::Op(Op::Bin(BinOp::ShiftLeft)) }
+    rule binop_shift_right() -> Token =
+      ">>" { Token::Op(Op::Bin(BinOp::ShiftRight)) }
 
 
     rule op() -> Token =
       unop_not() /
+      unop_bit_not() /
       op_add() /
       op_sub() /
       binop_mul() /
       binop_div() /
+      binop_mod() /
       binop_equals() /
       binop_not_equals() /
+      binop_shift_left() /
+      binop_shift_right() /
       binop_greater_equals() /
       binop_lesser_equals() /
       binop_greater() /
-      binop_lesser()
+      binop_lesser() /
+      binop_and() /
+      binop_or() /
+      binop_bit_and() /
+      binop_bit_or() /
+      binop_bit_xor()
 
 
     rule assignop_identity() -> Token =
-      "=" { Token::AssignOp(AssignOp::Identity) }
+      "=" !"=" { Token::AssignOp(AssignOp::Identity) }
 
     rule assignop_add() -> Token =
       "+=" { Token::AssignOp(AssignOp::Add) }
@@ -179,13 +200,14 @@ rule digit_bin() -> u64 = d:$(['0'..='1']) { d.parse().unwrap() }    rule digit_
       assignop_add() /
       assignop_sub() /
       assignop_mul() /
-      assignop_div()
+      assignop_div() /
+      assignop_mod()
 
 
     rule any() -> WithLineInfo<Token> =
       start:position!() token:(
-        literal_integer() /
         literal_float() /
+        literal_integer() /
         literal_character() /
         literal_string() /
         identifier() /
@@ -195,6 +217,7 @@ rule digit_bin() -> u64 = d:$(['0'..='1']) { d.parse().unwrap() }    rule digit_
         brackets() /
         semicolon() /
         separator() /
+        range() /
         dot() /
         comma() /
         colon() /
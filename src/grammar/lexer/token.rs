@@ -26,6 +26,7 @@ pub enum Token {
   BracketClose,
 
   SemiColon,
+  Range,
   Dot,
   Comma,
   Colon,
@@ -56,6 +57,7 @@ impl Token {
       Token::BracketOpen => "[",
       Token::BracketClose => "]",
       Token::SemiColon => "semicolon",
+      Token::Range => "..",
       Token::Dot => ".",
       Token::Comma => ",",
       Token::Colon => ":",
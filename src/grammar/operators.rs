@@ -5,6 +5,7 @@ pub enum UnOp {
   Not,
   Negate,
   Identity,
+  BitNot,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -20,6 +21,13 @@ pub enum BinOp {
   LessOrEqual,
   Greater,
   GreaterOrEqual,
+  And,
+  Or,
+  BitAnd,
+  BitOr,
+  BitXor,
+  ShiftLeft,
+  ShiftRight,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -43,19 +51,22 @@ pub enum AssignOp {
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Precedence {
+  // `||` binds looser than `&&`, which binds looser than comparisons
+  Or,
+  And,
   Lowest,
+  // Bitwise operators bind tighter than comparisons, and looser than `+`
+  BitOr,
+  BitXor,
+  BitAnd,
+  Shift,
   Low,
   High,
 }
 
 impl Op {
   pub fn can_be_unary(&self) -> bool {
-    match self {
-      Op::Un(_) => true,
-      Op::RawAdd => true,
-      Op::RawSub => true,
-      _ => false,
-    }
+    matches!(self, Op::Un(_) | Op::RawAdd | Op::RawSub)
   }
 
   pub fn as_unary(&self) -> UnOp {
@@ -69,12 +80,7 @@ impl Op {
   }
 
   pub fn can_be_binary(&self) -> bool {
-    match self {
-      Op::Bin(_) => true,
-      Op::RawAdd => true,
-      Op::RawSub => true,
-      _ => false,
-    }
+    matches!(self, Op::Bin(_) | Op::RawAdd | Op::RawSub)
   }
 
   pub fn as_binary(&self) -> BinOp {
@@ -106,6 +112,13 @@ impl BinOp {
       BinOp::LessOrEqual => Precedence::Lowest,
       BinOp::Greater => Precedence::Lowest,
       BinOp::GreaterOrEqual => Precedence::Lowest,
+      BinOp::And => Precedence::And,
+      BinOp::Or => Precedence::Or,
+      BinOp::BitAnd => Precedence::BitAnd,
+      BinOp::BitOr => Precedence::BitOr,
+      BinOp::BitXor => Precedence::BitXor,
+      BinOp::ShiftLeft => Precedence::Shift,
+      BinOp::ShiftRight => Precedence::Shift,
     }
   }
 }
@@ -1,7 +1,9 @@
 use crate::{
   grammar::{
+    builtins::BuiltinType,
     identifier::{CallTarget, FullIdentifier, Identifier, Name, Type},
-    operators::{AssignOp, BinOp, UnOp}, semifier::resolver::NameResolver,
+    operators::{AssignOp, BinOp, UnOp},
+    semifier::{error::SemanticError, resolver::NameResolver},
   },
   report::location::WithLineInfo,
 };
@@ -15,9 +17,9 @@ pub struct TypedNameWithLineInfo {
 }
 
 #[derive(Debug, Clone, PartialEq)]
-pub struct TypedName {
+pub struct TypedName<I: Clone = Identifier> {
   pub name: Name,
-  pub typ: Type,
+  pub typ: Type<I>,
 }
 
 #[derive(Debug, Clone)]
@@ -27,9 +29,9 @@ pub struct OptionalTypedNameWithLineInfo {
 }
 
 #[derive(Debug, Clone)]
-pub struct OptionalTypedName {
+pub struct OptionalTypedName<I: Clone = Identifier> {
   pub name: Name,
-  pub typ: Option<Type>,
+  pub typ: Option<Type<I>>,
 }
 
 #[derive(Debug, Clone)]
@@ -39,10 +41,18 @@ pub enum Expression<I: Clone> {
   AtomFloat(WithLineInfo<f64>),
   AtomString(WithLineInfo<String>),
   AtomIdentifier(I),
+  // Struct literal, with fields in the order they are written
+  Struct(I, Vec<(WithLineInfo<Name>, Expression<I>)>),
+  Field(Box<Expression<I>>, WithLineInfo<Name>),
+  // Array literal, with line information spanning the brackets
+  Array(WithLineInfo<()>, Vec<Expression<I>>),
+  Index(Box<Expression<I>>, Box<Expression<I>>),
 
   Call(WithLineInfo<CallTarget<I>>, Vec<Expression<I>>),
 
   UnOp(WithLineInfo<UnOp>, Box<Expression<I>>),
+  // Conversion between numeric types, with line information on the type
+  Cast(Box<Expression<I>>, WithLineInfo<BuiltinType>),
   BinOp(Box<Expression<I>>, WithLineInfo<BinOp>, Box<Expression<I>>),
 }
 
@@ -50,7 +60,8 @@ pub enum Expression<I: Clone> {
 pub enum Node {
   Expression(Expression<Identifier>),
   Assignment {
-    target: Identifier,
+    // A variable, or a field or element of one, as in `p.x = 1`
+    target: Expression<Identifier>,
     op: WithLineInfo<AssignOp>,
     val: Expression<Identifier>,
   },
@@ -59,6 +70,12 @@ pub enum Node {
     val: Expression<Identifier>,
     mutable: bool,
   },
+  // Global known at compile time, inlined wherever it is used
+  ConstDecl {
+    name: WithLineInfo<Name>,
+    typ: WithLineInfo<Type>,
+    val: Expression<Identifier>,
+  },
   FnDecl {
     attributes: Vec<WithLineInfo<Attribute>>,
     name: WithLineInfo<Name>,
@@ -66,7 +83,35 @@ pub enum Node {
     ret_type: Option<WithLineInfo<Type>>,
     body: Vec<Node>,
   },
-  Return(Expression<Identifier>),
+  Return(WithLineInfo<Option<Expression<Identifier>>>),
+  If {
+    cond: Expression<Identifier>,
+    then: Vec<Node>,
+    // An `else if` is an `else` block holding a single `If`
+    otherwise: Option<Vec<Node>>,
+  },
+  While {
+    cond: Expression<Identifier>,
+    body: Vec<Node>,
+  },
+  Loop(Vec<Node>),
+  // Iterates over the integers from `start` included to `end` excluded
+  For {
+    var: WithLineInfo<Name>,
+    start: Expression<Identifier>,
+    range: WithLineInfo<()>,
+    end: Expression<Identifier>,
+    body: Vec<Node>,
+  },
+  // Iterates over the elements of an array
+  ForEach {
+    var: WithLineInfo<Name>,
+    iterable: Expression<Identifier>,
+    body: Vec<Node>,
+  },
+  Block(Vec<Node>),
+  Break(WithLineInfo<()>),
+  Continue(WithLineInfo<()>),
   ModDecl(WithLineInfo<Name>),
   UseDecl(Identifier),
   StructDecl {
@@ -84,7 +129,23 @@ impl<I: Clone> Expression<I> {
         ids.extend(right.dependencies());
         ids
       }
-      Expression::UnOp(_, expr) => expr.dependencies(),
+      Expression::UnOp(_, expr) | Expression::Cast(expr, _) => {
+        expr.dependencies()
+      }
+      Expression::Struct(_, fields) => fields
+        .iter()
+        .flat_map(|(_, val)| val.dependencies())
+        .collect(),
+      Expression::Field(expr, _) => expr.dependencies(),
+      Expression::Array(_, elements) => elements
+        .iter()
+        .flat_map(|element| element.dependencies())
+        .collect(),
+      Expression::Index(expr, index) => {
+        let mut ids = expr.dependencies();
+        ids.extend(index.dependencies());
+        ids
+      }
       Expression::Call(target, args) => {
         let mut ids = if let CallTarget::Declared(id) = &target.value {
           vec![id.clone()]
@@ -104,13 +165,26 @@ impl<I: Clone> Expression<I> {
       Expression::BinOp(left, _, right) => {
         left.first_call().or(right.first_call())
       }
-      Expression::UnOp(_, expr) => expr.first_call(),
+      Expression::UnOp(_, expr) | Expression::Cast(expr, _) => {
+        expr.first_call()
+      }
+      Expression::Struct(_, fields) => {
+        fields.iter().find_map(|(_, val)| val.first_call())
+      }
+      Expression::Field(expr, _) => expr.first_call(),
+      Expression::Array(_, elements) => {
+        elements.iter().find_map(|element| element.first_call())
+      }
+      Expression::Index(expr, index) => {
+        expr.first_call().or(index.first_call())
+      }
       _ => None,
     }
   }
 }
 
 impl TypedNameWithLineInfo {
+  #[allow(dead_code)]
   pub fn unwrap(self) -> TypedName {
     TypedName {
       name: self.name.unwrap(),
@@ -120,38 +194,156 @@ impl TypedNameWithLineInfo {
 }
 
 impl Expression<Identifier> {
-  pub fn resolve(self, resolver: &NameResolver) -> Expression<FullIdentifier> {
-    match self {
+  pub fn resolve(
+    self,
+    resolver: &NameResolver,
+  ) -> Result<Expression<WithLineInfo<FullIdentifier>>, Box<SemanticError>> {
+    Ok(match self {
       Expression::AtomIdentifier(id) => {
-        let full_id = resolver.resolve(&id);
+        let full_id = id.span(resolver.resolve(&id)?.id);
         Expression::AtomIdentifier(full_id)
       }
+      Expression::Struct(id, fields) => {
+        let full_id = id.span(resolver.resolve_type(&id)?);
+        let full_fields = fields
+          .into_iter()
+          .map(|(name, val)| Ok((name, val.resolve(resolver)?)))
+          .collect::<Result<_, Box<SemanticError>>>()?;
+        Expression::Struct(full_id, full_fields)
+      }
+      Expression::Field(expr, name) => {
+        Expression::Field(Box::new(expr.resolve(resolver)?), name)
+      }
+      Expression::Array(span, elements) => {
+        let full_elements = elements
+          .into_iter()
+          .map(|element| element.resolve(resolver))
+          .collect::<Result<_, _>>()?;
+        Expression::Array(span, full_elements)
+      }
+      Expression::Index(expr, index) => Expression::Index(
+        Box::new(expr.resolve(resolver)?),
+        Box::new(index.resolve(resolver)?),
+      ),
       Expression::Call(target, args) => {
         let full_target = match &target.value {
-          CallTarget::Declared(id) => CallTarget::Declared(resolver.resolve(&id)),
-          CallTarget::Builtin(name) => CallTarget::Builtin(name.clone()),
+          CallTarget::Declared(id) => {
+            CallTarget::Declared(id.span(resolver.resolve(id)?.id))
+          }
+          CallTarget::Builtin(name) => {
+            if let (true, Some(first)) = (name.mutates(), args.first()) {
+              first.check_place(resolver)?;
+            }
+            CallTarget::Builtin(*name)
+          }
         };
-        let full_args = args.into_iter().map(|arg| arg.resolve(resolver)).collect();
+        let full_args = args
+          .into_iter()
+          .map(|arg| arg.resolve(resolver))
+          .collect::<Result<_, _>>()?;
         Expression::Call(target.map(|_| full_target), full_args)
       }
       Expression::UnOp(op, expr) => {
-        let full_expr = expr.resolve(resolver);
+        let full_expr = expr.resolve(resolver)?;
         Expression::UnOp(op, Box::new(full_expr))
       }
+      Expression::Cast(expr, typ) => {
+        Expression::Cast(Box::new(expr.resolve(resolver)?), typ)
+      }
       Expression::BinOp(left, op, right) => {
-        let full_left = left.resolve(resolver);
-        let full_right = right.resolve(resolver);
+        let full_left = left.resolve(resolver)?;
+        let full_right = right.resolve(resolver)?;
         Expression::BinOp(Box::new(full_left), op, Box::new(full_right))
       }
       Expression::AtomBoolean(b) => Expression::AtomBoolean(b),
       Expression::AtomInteger(i) => Expression::AtomInteger(i),
       Expression::AtomFloat(f) => Expression::AtomFloat(f),
       Expression::AtomString(s) => Expression::AtomString(s),
+    })
+  }
+}
+
+impl Expression<Identifier> {
+  /// Checks that the expression designates a part of a mutable variable,
+  /// which can be modified in place
+  pub fn check_place(
+    &self,
+    resolver: &NameResolver,
+  ) -> Result<(), Box<SemanticError>> {
+    match self {
+      Expression::AtomIdentifier(id) => resolver.resolve_target(id).map(|_| ()),
+      Expression::Field(expr, _) | Expression::Index(expr, _) => {
+        expr.check_place(resolver)
+      }
+      _ => {
+        let span = self.clone().resolve(resolver)?.span();
+        Err(
+          SemanticError::new(
+            resolver.module(),
+            "Cannot modify a temporary value",
+            span,
+          )
+          .with_label("not a variable")
+          .into(),
+        )
+      }
+    }
+  }
+}
+
+impl Expression<WithLineInfo<FullIdentifier>> {
+  /// Line information spanning the whole expression, as long as it fits on
+  /// its first line
+  pub fn span(&self) -> WithLineInfo<()> {
+    let (first, last) = (self.leftmost(), self.rightmost());
+    let len = if first.line == last.line && last.column >= first.column {
+      last.column + last.len - first.column
+    } else {
+      first.len
+    };
+    WithLineInfo { len, ..first }
+  }
+
+  fn leftmost(&self) -> WithLineInfo<()> {
+    match self {
+      Expression::AtomBoolean(atom) => atom.clone().map(|_| ()),
+      Expression::AtomInteger(atom) => atom.clone().map(|_| ()),
+      Expression::AtomFloat(atom) => atom.clone().map(|_| ()),
+      Expression::AtomString(atom) => atom.clone().map(|_| ()),
+      Expression::AtomIdentifier(id) => id.clone().map(|_| ()),
+      Expression::Struct(id, _) => id.clone().map(|_| ()),
+      Expression::Field(expr, _) => expr.leftmost(),
+      Expression::Array(span, _) => span.clone(),
+      Expression::Index(expr, _) => expr.leftmost(),
+      Expression::Call(target, _) => target.clone().map(|_| ()),
+      Expression::UnOp(op, _) => op.clone().map(|_| ()),
+      Expression::Cast(expr, _) => expr.leftmost(),
+      Expression::BinOp(left, _, _) => left.leftmost(),
+    }
+  }
+
+  fn rightmost(&self) -> WithLineInfo<()> {
+    match self {
+      Expression::Call(target, args) => args
+        .last()
+        .map(|arg| arg.rightmost())
+        .unwrap_or_else(|| target.clone().map(|_| ())),
+      Expression::UnOp(_, expr) => expr.rightmost(),
+      Expression::Cast(_, typ) => typ.clone().map(|_| ()),
+      Expression::BinOp(_, _, right) => right.rightmost(),
+      Expression::Struct(id, fields) => fields
+        .last()
+        .map(|(_, val)| val.rightmost())
+        .unwrap_or_else(|| id.clone().map(|_| ())),
+      Expression::Field(_, name) => name.clone().map(|_| ()),
+      Expression::Index(_, index) => index.rightmost(),
+      _ => self.leftmost(),
     }
   }
 }
 
 impl OptionalTypedNameWithLineInfo {
+  #[allow(dead_code)]
   pub fn unwrap(self) -> OptionalTypedName {
     OptionalTypedName {
       name: self.name.unwrap(),
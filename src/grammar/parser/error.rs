@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::Path;
 
 use peg::error::ExpectedSet;
 
@@ -21,14 +21,14 @@ pub struct ParserError {
 }
 
 impl ParserError {
-  pub fn get_report(&self, path: &PathBuf, source: &str) -> Message {
+  pub fn get_report(&self, path: &Path, source: &str) -> Message {
     let expected_count = self.expected.tokens().count();
     let expected_list = self.expected.tokens().collect::<Vec<_>>().join(", ");
 
     let line = source.lines().nth(self.line - 1).unwrap();
 
     let line =
-      HighlightedLine::new(self.line, &line, LineType::Source).with_highlight(
+      HighlightedLine::new(self.line, line, LineType::Source).with_highlight(
         Highlight::new(self.column, self.len, HighlightType::Focus),
       );
     let expected = if expected_count > 1 {
@@ -42,7 +42,7 @@ impl ParserError {
       MessageType::Error,
     )
     .with_meta(MessageMeta::FileLocation(
-      path.clone(),
+      path.to_path_buf(),
       self.line,
       self.column,
     ))
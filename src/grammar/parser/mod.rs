@@ -1,6 +1,6 @@
 pub mod ast;
-pub mod error;
 pub mod attributes;
+pub mod error;
 mod helper;
 mod peg;
 
@@ -31,3 +31,55 @@ impl Parser {
     })
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::{
+    ast::{Expression, Node},
+    Parser,
+  };
+  use crate::grammar::{lexer::Lexer, operators::AssignOp};
+
+  /// The statements of the body of `fn main` in `source`
+  fn body(source: &str) -> Vec<Node> {
+    let tokens = Lexer.lex(&format!("fn main() {{ {source} }}")).unwrap();
+    match Parser.parse(&tokens).unwrap().remove(0) {
+      Node::FnDecl { body, .. } => body,
+      node => panic!("expected a function, found {node:?}"),
+    }
+  }
+
+  #[test]
+  fn compound_assignment_to_field() {
+    let Node::Assignment { target, op, .. } = &body("p.x += 1;")[0] else {
+      panic!("expected an assignment");
+    };
+    assert_eq!(op.value, AssignOp::Add);
+    let Expression::Field(base, field) = target else {
+      panic!("expected a field target, found {target:?}");
+    };
+    assert!(matches!(**base, Expression::AtomIdentifier(_)));
+    assert_eq!(field.value, "x");
+  }
+
+  #[test]
+  fn compound_assignment_to_element() {
+    let Node::Assignment { target, op, .. } = &body("list[i] *= 2;")[0] else {
+      panic!("expected an assignment");
+    };
+    assert_eq!(op.value, AssignOp::Mul);
+    let Expression::Index(base, index) = target else {
+      panic!("expected an index target, found {target:?}");
+    };
+    assert!(matches!(**base, Expression::AtomIdentifier(_)));
+    assert!(matches!(**index, Expression::AtomIdentifier(_)));
+  }
+
+  #[test]
+  fn remainder_assignment() {
+    let Node::Assignment { op, .. } = &body("x %= 3;")[0] else {
+      panic!("expected an assignment");
+    };
+    assert_eq!(op.value, AssignOp::Mod);
+  }
+}
@@ -1,9 +1,11 @@
-use super::ast::{Expression, Node, OptionalTypedNameWithLineInfo, TypedNameWithLineInfo};
+use super::ast::{
+  Expression, Node, OptionalTypedNameWithLineInfo, TypedNameWithLineInfo,
+};
 use super::attributes::Attribute;
 use super::helper::LineInfoFn;
 use crate::{
   grammar::{
-    builtins::Builtin,
+    builtins::{Builtin, BuiltinType},
     identifier::{CallTarget, Identifier, Name, Type},
     keywords::Keyword,
     lexer::token::Token,
@@ -45,7 +47,11 @@ peg::parser! {
     rule atom_integer() -> WithLineInfo<isize> =
       start:position!()
       [Token::LiteralInteger(value)]
-      end:position!() { line_info.tag(*value as isize, start, end) }
+      end:position!() {
+        // Too large literals are reported by the type checker
+        let value = isize::try_from(*value).unwrap_or(isize::MAX);
+        line_info.tag(value, start, end)
+      }
     rule atom_float() -> WithLineInfo<f64> =
       start:position!()
       [Token::LiteralFloat(value)]
@@ -58,9 +64,22 @@ peg::parser! {
       start:position!()
       t:(
         [Token::Identifier(typ)] { Type::Declared(typ.clone()) } /
-        [Token::Builtin(Builtin::Type(btype))] { Type::Builtin(*btype) }
+        [Token::Builtin(Builtin::Type(btype))] { Type::Builtin(*btype) } /
+        [Token::BracketOpen] _? element:typ() _? [Token::BracketClose] {
+          Type::Array(Box::new(element.value))
+        }
       )
       end:position!() { line_info.tag(t, start, end) }
+    rule builtin_type() -> WithLineInfo<BuiltinType> =
+      start:position!()
+      [Token::Builtin(Builtin::Type(btype))]
+      end:position!() { line_info.tag(*btype, start, end) }
+    rule array() -> (WithLineInfo<()>, Vec<Expression<Identifier>>) =
+      start:position!()
+      [Token::BracketOpen] _?
+      elements:expression_seq() _?
+      [Token::BracketClose]
+      end:position!() { (line_info.tag((), start, end), elements) }
     // Passthrough lexer
     rule identifier() -> Identifier = [Token::Identifier(name)] { name.clone() }
     // Separators
@@ -74,8 +93,8 @@ peg::parser! {
       [Token::Colon] _?
       typ:typ() {
         TypedNameWithLineInfo {
-          name: name,
-          typ: typ,
+          name,
+          typ,
         }
       }
 
@@ -89,13 +108,41 @@ peg::parser! {
       d:(typed_name() ** param_sep()) param_sep()? { d }
     rule expression_seq() -> Vec<Expression<Identifier>> =
       e:(expression() ** param_sep()) param_sep()? { e }
+    // At least one field, so that `if x {}` is not a struct literal
+    rule field_inits() -> Vec<(WithLineInfo<Name>, Expression<Identifier>)> =
+      f:((name:name() _? [Token::Colon] _? val:expression() { (name, val) })
+        ++ param_sep()) param_sep()? { f }
 
     // Expression: This beast has a section for itself
     rule expression() -> Expression<Identifier> = precedence! {
+      x:(@) _? op:binop(Precedence::Or) _? y:@ {
+        Expression::BinOp(x.into(), op, y.into())
+      }
+      --
+      x:(@) _? op:binop(Precedence::And) _? y:@ {
+        Expression::BinOp(x.into(), op, y.into())
+      }
+      --
       x:(@) _? op:binop(Precedence::Lowest) _? y:@ {
         Expression::BinOp(x.into(), op, y.into())
       }
       --
+      x:(@) _? op:binop(Precedence::BitOr) _? y:@ {
+        Expression::BinOp(x.into(), op, y.into())
+      }
+      --
+      x:(@) _? op:binop(Precedence::BitXor) _? y:@ {
+        Expression::BinOp(x.into(), op, y.into())
+      }
+      --
+      x:(@) _? op:binop(Precedence::BitAnd) _? y:@ {
+        Expression::BinOp(x.into(), op, y.into())
+      }
+      --
+      x:(@) _? op:binop(Precedence::Shift) _? y:@ {
+        Expression::BinOp(x.into(), op, y.into())
+      }
+      --
       x:(@) _? op:binop(Precedence::Low) _? y:@ {
         Expression::BinOp(x.into(), op, y.into())
       }
@@ -104,8 +151,19 @@ peg::parser! {
         Expression::BinOp(x.into(), op, y.into())
       }
       --
+      x:(@) _ [Token::Keyword(Keyword::As)] _ typ:builtin_type() {
+        Expression::Cast(x.into(), typ)
+      }
+      --
       op:unop() _? x:@ { Expression::UnOp(op, x.into()) }
       --
+      x:(@) _? [Token::Dot] _? field:name() {
+        Expression::Field(x.into(), field)
+      }
+      x:(@) _? [Token::BracketOpen] _? index:expression() _? [Token::BracketClose] {
+        Expression::Index(x.into(), index.into())
+      }
+      --
       target:call_target() _? [Token::ParenOpen] _? args:expression_seq() _? [Token::ParenClose] {
         Expression::Call(target, args)
       }
@@ -114,7 +172,11 @@ peg::parser! {
       atom:atom_integer() { Expression::AtomInteger(atom) }
       atom:atom_float() { Expression::AtomFloat(atom) }
       atom:atom_string() { Expression::AtomString(atom) }
+      id:identifier() _? [Token::BraceOpen] _? fields:field_inits() _? [Token::BraceClose] {
+        Expression::Struct(id, fields)
+      }
       atom:identifier() { Expression::AtomIdentifier(atom) }
+      array:array() { Expression::Array(array.0, array.1) }
       --
       [Token::ParenOpen] _? x:expression() _? [Token::ParenClose] { x }
     }
@@ -134,29 +196,116 @@ peg::parser! {
         }
       }
 
+    // Places that can be assigned to: variables, their fields and elements
+    rule place() -> Expression<Identifier> = precedence! {
+      x:(@) _? [Token::Dot] _? field:name() {
+        Expression::Field(x.into(), field)
+      }
+      x:(@) _? [Token::BracketOpen] _? index:expression() _? [Token::BracketClose] {
+        Expression::Index(x.into(), index.into())
+      }
+      --
+      id:identifier() { Expression::AtomIdentifier(id) }
+    }
+
     rule assignment() -> Node =
-      target:identifier() _?
+      target:place() _?
       op:assignop() _?
       val:expression() {
         Node::Assignment { target, op, val }
       }
     rule ret() -> Node =
-      [Token::Keyword(Keyword::Ret)] _
-      val:expression() {
-        Node::Return(val)
+      start:position!()
+      [Token::Keyword(Keyword::Ret)]
+      val:(_ val:expression() { val })?
+      end:position!() {
+        Node::Return(line_info.tag(val, start, end))
+      }
+
+    rule jump() -> Node =
+      start:position!()
+      [Token::Keyword(Keyword::Break)]
+      end:position!() {
+        Node::Break(line_info.tag((), start, end))
+      } /
+      start:position!()
+      [Token::Keyword(Keyword::Continue)]
+      end:position!() {
+        Node::Continue(line_info.tag((), start, end))
       }
 
     rule statement() -> Node =
-      e:expression() { Node::Expression(e) } /
       var_decl() /
       assignment() /
-      ret()
+      ret() /
+      jump() /
+      e:expression() { Node::Expression(e) }
 
-    rule statement_seq() -> Vec<Node> =
-      s:(s:(statement() ** stmt_sep()) stmt_sep() { s })? {
-        s.unwrap_or(Vec::new())
+    // Statements ending with a block, which need no semicolon
+    rule if_stmt() -> Node =
+      [Token::Keyword(Keyword::If)] _?
+      cond:expression() _?
+      then:block()
+      otherwise:(
+        _? [Token::Keyword(Keyword::Else)] _?
+        otherwise:(elif:if_stmt() { vec![elif] } / block()) { otherwise }
+      )? {
+        Node::If { cond, then, otherwise }
+      }
+
+    rule while_stmt() -> Node =
+      [Token::Keyword(Keyword::While)] _?
+      cond:expression() _?
+      body:block() {
+        Node::While { cond, body }
+      }
+
+    rule loop_stmt() -> Node =
+      [Token::Keyword(Keyword::Loop)] _?
+      body:block() {
+        Node::Loop(body)
+      }
+
+    rule for_stmt() -> Node =
+      [Token::Keyword(Keyword::For)] _
+      var:name() _
+      [Token::Keyword(Keyword::In)] _?
+      start:expression() _?
+      range_start:position!() [Token::Range] range_end:position!() _?
+      end:expression() _?
+      body:block() {
+        let range = line_info.tag((), range_start, range_end);
+        Node::For { var, start, range, end, body }
       }
 
+    rule for_each_stmt() -> Node =
+      [Token::Keyword(Keyword::For)] _
+      var:name() _
+      [Token::Keyword(Keyword::In)] _?
+      iterable:expression() _?
+      body:block() {
+        Node::ForEach { var, iterable, body }
+      }
+
+    rule block_statement() -> Node =
+      body:block() { Node::Block(body) } /
+      if_stmt() /
+      while_stmt() /
+      loop_stmt() /
+      for_stmt() /
+      for_each_stmt()
+
+    rule statement_seq() -> Vec<Node> =
+      s:(
+        s:block_statement() (stmt_sep() / _?) { s } /
+        s:statement() stmt_sep() { s }
+      )* { s }
+
+    rule block() -> Vec<Node> =
+      [Token::BraceOpen] _?
+      body:statement_seq() _?
+      [Token::BraceClose] { body }
+
     // Attributes
     rule attribute() -> WithLineInfo<Attribute> =
       [Token::Hash] [Token::BracketOpen] _?
@@ -186,14 +335,21 @@ peg::parser! {
       params:params_decl() _?
       [Token::ParenClose] _?
       ret_type:return_spec()? _?
-      [Token::BraceOpen] _?
-      body:statement_seq() _?
-      [Token::BraceClose] {
+      body:block() {
         Node::FnDecl { attributes, name, params, ret_type, body }
       }
 
     rule glob_var_decl() -> Node = d:var_decl() stmt_sep() { d }
 
+    rule glob_const_decl() -> Node =
+      [Token::Keyword(Keyword::Const)] _
+      name:name() _?
+      [Token::Colon] _? typ:typ() _?
+      [Token::AssignOp(AssignOp::Identity)] _?
+      val:expression() stmt_sep() {
+        Node::ConstDecl { name, typ, val }
+      }
+
     rule glob_mod_decl() -> Node =
       [Token::Keyword(Keyword::Mod)] _
       name:name() stmt_sep() {
@@ -217,6 +373,7 @@ peg::parser! {
     rule glob_decl() -> Node =
       glob_fn_decl() /
       glob_var_decl() /
+      glob_const_decl() /
       glob_mod_decl() /
       glob_struct_decl() /
       glob_use_decl()
@@ -1,42 +1,108 @@
 use crate::{
   grammar::{
-    builtins::BuiltinType,
-    identifier::{FullIdentifier, Name, Type},
-    operators::AssignOp,
+    builtins::{BuiltinFn, BuiltinType},
+    identifier::{CallTarget, FullIdentifier, Name, Type},
+    operators::{AssignOp, BinOp},
     parser::{
       ast::{Expression, Node, OptionalTypedName, TypedName},
       attributes::Attribute,
     },
   },
-  report::message::Message,
+  report::{location::WithLineInfo, message::Message},
 };
 
-use super::resolver::NameResolver;
+use super::{
+  consteval::Constant,
+  error::SemanticError,
+  module::ModulePath,
+  resolver::{Binding, NameResolver, ScopeKind},
+};
+
+pub type ResolvedExpression = Expression<WithLineInfo<FullIdentifier>>;
 
 #[derive(Debug, Clone)]
 pub struct FnDecl {
+  pub module: ModulePath,
   pub attributes: Vec<Attribute>,
-  pub name: Name,
-  pub params: Vec<TypedName>,
-  pub ret_type: Type,
+  pub name: WithLineInfo<Name>,
+  pub params: Vec<TypedName<FullIdentifier>>,
+  pub ret_type: Type<FullIdentifier>,
+  pub ret_span: Option<WithLineInfo<()>>,
   pub dependencies: Vec<FullIdentifier>,
-  pub locals: Vec<OptionalTypedName>,
+  // Types of locals declared without annotation are filled by the type checker
+  pub locals: Vec<OptionalTypedName<FullIdentifier>>,
   pub body: Vec<FnStatement>,
 }
 
+#[derive(Debug, Clone)]
+pub struct GlobalDecl {
+  pub module: ModulePath,
+  pub name: WithLineInfo<Name>,
+  pub typ: Option<WithLineInfo<Type<FullIdentifier>>>,
+  pub val: ResolvedExpression,
+  // Value of the initializer if known at compile time, filled by the
+  // constant evaluator
+  pub value: Option<Constant>,
+  #[allow(dead_code)]
+  pub mutable: bool,
+  // Declared with `const`, so inlined instead of stored
+  pub constant: bool,
+  pub dependencies: Vec<FullIdentifier>,
+}
+
+#[derive(Debug, Clone)]
+pub struct StructDecl {
+  pub module: ModulePath,
+  pub name: WithLineInfo<Name>,
+  pub fields: Vec<TypedName<FullIdentifier>>,
+}
+
 #[derive(Debug, Clone)]
 pub enum FnStatement {
+  VarDecl {
+    name: WithLineInfo<Name>,
+    typ: Option<WithLineInfo<Type<FullIdentifier>>>,
+    val: ResolvedExpression,
+  },
   Assignment {
-    target: FullIdentifier,
-    op: AssignOp,
-    val: Expression<FullIdentifier>,
+    target: ResolvedExpression,
+    op: WithLineInfo<AssignOp>,
+    val: ResolvedExpression,
+  },
+  SideEffect(ResolvedExpression),
+  Return(WithLineInfo<Option<ResolvedExpression>>),
+  If {
+    cond: ResolvedExpression,
+    then: Vec<FnStatement>,
+    otherwise: Vec<FnStatement>,
   },
-  SideEffect(Expression<FullIdentifier>),
-  Return(Expression<FullIdentifier>),
+  // Runs `body` then `step` as long as `cond` holds, forever without one.
+  // `continue` skips the rest of the body but not the step.
+  Loop {
+    cond: Option<ResolvedExpression>,
+    body: Vec<FnStatement>,
+    step: Vec<FnStatement>,
+  },
+  Break,
+  Continue,
 }
 
 impl FnDecl {
-  pub fn from_function_node(node: Node, resolver: &mut NameResolver) -> Self {
+  pub fn returns_void(&self) -> bool {
+    self.ret_type == Type::void()
+  }
+
+  /// Whether the function is part of the datapack's interface, and so is
+  /// compiled even if nothing calls it
+  pub fn independent(&self) -> bool {
+    self.attributes.iter().any(Attribute::independent)
+  }
+
+  pub fn from_function_node(
+    module: ModulePath,
+    node: Node,
+    resolver: &mut NameResolver,
+  ) -> Result<Self, Box<SemanticError>> {
     if let Node::FnDecl {
       attributes,
       name,
@@ -45,59 +111,488 @@ impl FnDecl {
       body,
     } = node
     {
-      resolver.push_scope();
+      resolver.push_scope(ScopeKind::Function);
+      for param in &params {
+        resolver.decl_local(&param.name, Binding::Param);
+      }
       let mut locals = Vec::new();
       let mut deps = Vec::new();
-      let mut minbody = Vec::new();
-      for node in body {
-        match node {
-          Node::VarDecl { typ, val, .. } => {
-            let name = typ.name.clone().unwrap();
-            let resolved_expr = val.resolve(resolver);
-            deps.extend_from_slice(&resolved_expr.dependencies());
-            resolver.decl_local(name.clone());
-            locals.push(typ.unwrap());
-
-            minbody.push(FnStatement::Assignment {
-              target: FullIdentifier::Local(name),
-              op: AssignOp::Identity,
-              val: resolved_expr,
-            });
-          }
-          Node::Assignment { target, op, val } => {
-            let resolved_target = resolver.resolve(&target);
-            let resolved_val = val.resolve(resolver);
-            deps.push(resolved_target.clone());
-            deps.extend_from_slice(&resolved_val.dependencies());
-            minbody.push(FnStatement::Assignment {
-              target: resolved_target,
-              op: op.unwrap(),
-              val: resolved_val,
-            });
-          }
-          Node::Return(expr) => {
-            let resolved_expr = expr.resolve(resolver);
-            deps.extend_from_slice(&resolved_expr.dependencies());
-            minbody.push(FnStatement::Return(resolved_expr));
-          }
-          _ => Message::compiler_bug("Unexpected node in function body")
-            .report_and_exit(1),
-        };
-      }
+      let minbody =
+        lower_block(body, ScopeKind::Block, resolver, &mut locals, &mut deps)?;
       resolver.pop_scope();
-      Self {
+      let params = params
+        .into_iter()
+        .map(|param| {
+          Ok(TypedName {
+            name: param.name.unwrap(),
+            typ: param.typ.unwrap().resolve(resolver)?,
+          })
+        })
+        .collect::<Result<_, Box<SemanticError>>>()?;
+      let ret_span = ret_type.as_ref().map(|ret| ret.clone().map(|_| ()));
+      let ret_type = match ret_type {
+        Some(ret) => ret.unwrap().resolve(resolver)?,
+        None => Type::void(),
+      };
+      Ok(Self {
+        module,
         attributes: attributes.into_iter().map(|att| att.unwrap()).collect(),
-        name: name.unwrap(),
-        params: params.into_iter().map(|param| param.unwrap()).collect(),
-        ret_type: ret_type
-          .map(|ret| ret.unwrap())
-          .unwrap_or_else(|| Type::Builtin(BuiltinType::Void)),
+        name,
+        params,
+        ret_type,
+        ret_span,
         dependencies: deps,
         locals,
         body: minbody,
-      }
+      })
     } else {
       Message::compiler_bug("Expected FnDecl node").report_and_exit(1)
     }
   }
 }
+
+impl FnStatement {
+  /// Whether no path through the statement reaches the next one, because
+  /// it ends with a `return`, `break` or `continue`, or never ends
+  pub fn always_jumps(&self) -> bool {
+    match self {
+      FnStatement::Return(_) | FnStatement::Break | FnStatement::Continue => {
+        true
+      }
+      FnStatement::If {
+        then, otherwise, ..
+      } => always_jumps(then) && always_jumps(otherwise),
+      FnStatement::Loop {
+        cond: None, body, ..
+      } => !may_break(body),
+      _ => false,
+    }
+  }
+
+  /// Whether some path through the statement ends with a `return`, or with
+  /// a `break` or `continue` of an enclosing loop
+  pub fn may_jump(&self) -> bool {
+    match self {
+      FnStatement::Return(_) | FnStatement::Break | FnStatement::Continue => {
+        true
+      }
+      FnStatement::If {
+        then, otherwise, ..
+      } => may_jump(then) || may_jump(otherwise),
+      FnStatement::Loop { body, .. } => may_return(body),
+      _ => false,
+    }
+  }
+
+  /// Whether some path through the statement ends with a `return`
+  pub fn may_return(&self) -> bool {
+    match self {
+      FnStatement::Return(_) => true,
+      FnStatement::If {
+        then, otherwise, ..
+      } => may_return(then) || may_return(otherwise),
+      FnStatement::Loop { body, .. } => may_return(body),
+      _ => false,
+    }
+  }
+
+  /// Whether the statement may break out of the enclosing loop
+  pub fn may_break(&self) -> bool {
+    match self {
+      FnStatement::Break => true,
+      FnStatement::If {
+        then, otherwise, ..
+      } => may_break(then) || may_break(otherwise),
+      _ => false,
+    }
+  }
+}
+
+pub fn always_jumps(body: &[FnStatement]) -> bool {
+  body.iter().any(FnStatement::always_jumps)
+}
+
+pub fn may_jump(body: &[FnStatement]) -> bool {
+  body.iter().any(FnStatement::may_jump)
+}
+
+pub fn may_return(body: &[FnStatement]) -> bool {
+  body.iter().any(FnStatement::may_return)
+}
+
+pub fn may_break(body: &[FnStatement]) -> bool {
+  body.iter().any(FnStatement::may_break)
+}
+
+/// Resolves the statements of a block in a new scope, collecting the locals
+/// it declares and the identifiers it depends on
+fn lower_block(
+  body: Vec<Node>,
+  kind: ScopeKind,
+  resolver: &mut NameResolver,
+  locals: &mut Vec<OptionalTypedName<FullIdentifier>>,
+  deps: &mut Vec<FullIdentifier>,
+) -> Result<Vec<FnStatement>, Box<SemanticError>> {
+  resolver.push_scope(kind);
+  let mut minbody = Vec::new();
+  for node in body {
+    match node {
+      Node::VarDecl { typ, val, mutable } => {
+        let resolved_expr = val.resolve(resolver)?;
+        let resolved_typ = typ
+          .typ
+          .map(|typ| typ.try_map(|typ| typ.resolve(resolver)))
+          .transpose()?;
+        deps.extend(
+          resolved_expr
+            .dependencies()
+            .into_iter()
+            .map(WithLineInfo::unwrap),
+        );
+        let slot = resolver.decl_local(&typ.name, Binding::Let { mutable });
+        locals.push(OptionalTypedName {
+          name: slot.clone(),
+          typ: resolved_typ.clone().map(|typ| typ.unwrap()),
+        });
+
+        minbody.push(FnStatement::VarDecl {
+          name: typ.name.map(|_| slot),
+          typ: resolved_typ,
+          val: resolved_expr,
+        });
+      }
+      Node::Assignment { target, op, val } => {
+        target.check_place(resolver)?;
+        let resolved_target = target.resolve(resolver)?;
+        let resolved_val = val.resolve(resolver)?;
+        deps.extend(
+          resolved_target
+            .dependencies()
+            .into_iter()
+            .chain(resolved_val.dependencies())
+            .map(WithLineInfo::unwrap),
+        );
+        minbody.push(FnStatement::Assignment {
+          target: resolved_target,
+          op,
+          val: resolved_val,
+        });
+      }
+      Node::Expression(expr) => {
+        let resolved_expr = expr.resolve(resolver)?;
+        deps.extend(
+          resolved_expr
+            .dependencies()
+            .into_iter()
+            .map(WithLineInfo::unwrap),
+        );
+        minbody.push(FnStatement::SideEffect(resolved_expr));
+      }
+      Node::Return(expr) => {
+        let resolved_expr = expr.try_map(|expr| {
+          expr.map(|expr| expr.resolve(resolver)).transpose()
+        })?;
+        if let Some(expr) = &resolved_expr.value {
+          deps
+            .extend(expr.dependencies().into_iter().map(WithLineInfo::unwrap));
+        }
+        minbody.push(FnStatement::Return(resolved_expr));
+      }
+      Node::If {
+        cond,
+        then,
+        otherwise,
+      } => {
+        let resolved_cond = cond.resolve(resolver)?;
+        deps.extend(
+          resolved_cond
+            .dependencies()
+            .into_iter()
+            .map(WithLineInfo::unwrap),
+        );
+        let then = lower_block(then, ScopeKind::Block, resolver, locals, deps)?;
+        let otherwise = match otherwise {
+          Some(otherwise) => {
+            lower_block(otherwise, ScopeKind::Block, resolver, locals, deps)?
+          }
+          None => Vec::new(),
+        };
+        minbody.push(FnStatement::If {
+          cond: resolved_cond,
+          then,
+          otherwise,
+        });
+      }
+      Node::While { cond, body } => {
+        let resolved_cond = cond.resolve(resolver)?;
+        deps.extend(
+          resolved_cond
+            .dependencies()
+            .into_iter()
+            .map(WithLineInfo::unwrap),
+        );
+        let body = lower_block(body, ScopeKind::Loop, resolver, locals, deps)?;
+        minbody.push(FnStatement::Loop {
+          cond: Some(resolved_cond),
+          body,
+          step: Vec::new(),
+        });
+      }
+      Node::Loop(body) => {
+        let body = lower_block(body, ScopeKind::Loop, resolver, locals, deps)?;
+        minbody.push(FnStatement::Loop {
+          cond: None,
+          body,
+          step: Vec::new(),
+        });
+      }
+      Node::For {
+        var,
+        start,
+        range,
+        end,
+        body,
+      } => {
+        let start = start.resolve(resolver)?;
+        let end = end.resolve(resolver)?;
+        // The bounds cannot see the variable, which lives in its own scope
+        resolver.push_scope(ScopeKind::Block);
+        let slot = resolver.decl_local(&var, Binding::Loop);
+        let body = lower_block(body, ScopeKind::Loop, resolver, locals, deps);
+        resolver.pop_scope();
+        let body = body?;
+        let var = var.map(|_| slot);
+        // The end of the range is evaluated once, into a hidden local whose
+        // name cannot clash with user variables
+        let end_name = format!("for{}-end", locals.len());
+
+        let mut bounds = Vec::new();
+        for (name, expr) in [
+          (var.clone(), start),
+          (var.clone().map(|_| end_name.clone()), end),
+        ] {
+          deps
+            .extend(expr.dependencies().into_iter().map(WithLineInfo::unwrap));
+          let int_type = range.clone().map(|_| Type::Builtin(BuiltinType::Int));
+          locals.push(OptionalTypedName {
+            name: name.value.clone(),
+            typ: Some(int_type.value.clone()),
+          });
+          bounds.push(FnStatement::VarDecl {
+            name,
+            typ: Some(int_type),
+            val: expr,
+          });
+        }
+
+        let local = |name: &Name| {
+          Expression::AtomIdentifier(
+            var.clone().map(|_| FullIdentifier::Local(name.clone())),
+          )
+        };
+        let cond = Expression::BinOp(
+          Box::new(local(&var.value)),
+          var.clone().map(|_| BinOp::Less),
+          Box::new(local(&end_name)),
+        );
+        let step = FnStatement::Assignment {
+          target: local(&var.value),
+          op: var.clone().map(|_| AssignOp::Add),
+          val: Expression::AtomInteger(var.clone().map(|_| 1)),
+        };
+        minbody.extend(bounds);
+        minbody.push(FnStatement::Loop {
+          cond: Some(cond),
+          body,
+          step: vec![step],
+        });
+      }
+      Node::ForEach {
+        var,
+        iterable,
+        body,
+      } => {
+        let iterable = iterable.resolve(resolver)?;
+        resolver.push_scope(ScopeKind::Block);
+        let slot = resolver.decl_local(&var, Binding::Loop);
+        let body = lower_block(body, ScopeKind::Loop, resolver, locals, deps);
+        resolver.pop_scope();
+        let body = body?;
+        deps.extend(
+          iterable
+            .dependencies()
+            .into_iter()
+            .map(WithLineInfo::unwrap),
+        );
+        // The loop walks a copy of the array, so that the body may modify the
+        // original without disturbing the iteration
+        let span = iterable.span();
+        let count = locals.len();
+        let [list, index, end] =
+          ["list", "index", "end"].map(|what| format!("for{count}-{what}"));
+        let local = |name: &Name| {
+          Expression::AtomIdentifier(
+            span.clone().map(|_| FullIdentifier::Local(name.clone())),
+          )
+        };
+        let int_type = span.clone().map(|_| Type::Builtin(BuiltinType::Int));
+        let length = Expression::Call(
+          span.clone().map(|_| CallTarget::Builtin(BuiltinFn::Len)),
+          vec![local(&list)],
+        );
+        for (name, typ, val) in [
+          (&list, None, iterable),
+          (
+            &index,
+            Some(int_type.clone()),
+            Expression::AtomInteger(span.clone().map(|_| 0)),
+          ),
+          (&end, Some(int_type), length),
+        ] {
+          locals.push(OptionalTypedName {
+            name: name.clone(),
+            typ: typ.clone().map(WithLineInfo::unwrap),
+          });
+          minbody.push(FnStatement::VarDecl {
+            name: span.clone().map(|_| name.clone()),
+            typ,
+            val,
+          });
+        }
+
+        locals.push(OptionalTypedName {
+          name: slot.clone(),
+          typ: None,
+        });
+        let element = FnStatement::VarDecl {
+          name: var.map(|_| slot),
+          typ: None,
+          val: Expression::Index(
+            Box::new(local(&list)),
+            Box::new(local(&index)),
+          ),
+        };
+        let cond = Expression::BinOp(
+          Box::new(local(&index)),
+          span.clone().map(|_| BinOp::Less),
+          Box::new(local(&end)),
+        );
+        let step = FnStatement::Assignment {
+          target: local(&index),
+          op: span.clone().map(|_| AssignOp::Add),
+          val: Expression::AtomInteger(span.clone().map(|_| 1)),
+        };
+        minbody.push(FnStatement::Loop {
+          cond: Some(cond),
+          body: std::iter::once(element).chain(body).collect(),
+          step: vec![step],
+        });
+      }
+      Node::Block(body) => {
+        // Scopes only matter to name resolution, the statements of a block
+        // run as part of the enclosing one
+        let body = lower_block(body, ScopeKind::Block, resolver, locals, deps)?;
+        minbody.extend(body);
+      }
+      Node::Break(span) => {
+        resolver.jump("break", span)?;
+        minbody.push(FnStatement::Break);
+      }
+      Node::Continue(span) => {
+        resolver.jump("continue", span)?;
+        minbody.push(FnStatement::Continue);
+      }
+      _ => Message::compiler_bug("Unexpected node in function body")
+        .report_and_exit(1),
+    };
+  }
+  resolver.pop_scope();
+  Ok(minbody)
+}
+
+impl StructDecl {
+  pub fn from_struct_node(
+    module: ModulePath,
+    node: Node,
+    resolver: &NameResolver,
+  ) -> Result<Self, Box<SemanticError>> {
+    if let Node::StructDecl { name, fields } = node {
+      for (index, field) in fields.iter().enumerate() {
+        let previous = fields[..index]
+          .iter()
+          .find(|previous| previous.name.value == field.name.value);
+        if let Some(previous) = previous {
+          return Err(
+            SemanticError::new(
+              &module,
+              &format!(
+                "Field `{}` is declared multiple times",
+                field.name.value
+              ),
+              field.name.clone().map(|_| ()),
+            )
+            .with_label("redeclared here")
+            .with_helper(
+              previous.name.clone().map(|_| ()),
+              "first declared here",
+            )
+            .into(),
+          );
+        }
+      }
+      let fields = fields
+        .into_iter()
+        .map(|field| {
+          Ok(TypedName {
+            name: field.name.unwrap(),
+            typ: field.typ.unwrap().resolve(resolver)?,
+          })
+        })
+        .collect::<Result<_, Box<SemanticError>>>()?;
+      Ok(Self {
+        module,
+        name,
+        fields,
+      })
+    } else {
+      Message::compiler_bug("Expected StructDecl node").report_and_exit(1)
+    }
+  }
+
+  pub fn field(&self, name: &Name) -> Option<&TypedName<FullIdentifier>> {
+    self.fields.iter().find(|field| field.name == *name)
+  }
+}
+
+impl GlobalDecl {
+  pub fn from_var_node(
+    module: ModulePath,
+    node: Node,
+    resolver: &NameResolver,
+  ) -> Result<Self, Box<SemanticError>> {
+    let (name, typ, val, mutable, constant) = match node {
+      Node::VarDecl { typ, val, mutable } => {
+        (typ.name, typ.typ, val, mutable, false)
+      }
+      Node::ConstDecl { name, typ, val } => (name, Some(typ), val, false, true),
+      _ => Message::compiler_bug("Expected VarDecl node").report_and_exit(1),
+    };
+    let val = val.resolve(resolver)?;
+    Ok(Self {
+      module,
+      name,
+      typ: typ
+        .map(|typ| typ.try_map(|typ| typ.resolve(resolver)))
+        .transpose()?,
+      dependencies: val
+        .dependencies()
+        .into_iter()
+        .map(WithLineInfo::unwrap)
+        .collect(),
+      val,
+      value: None,
+      mutable,
+      constant,
+    })
+  }
+}
@@ -0,0 +1,676 @@
+use super::{
+  ast::{FnStatement, ResolvedExpression},
+  error::SemanticError,
+  program::Program,
+};
+use crate::{
+  grammar::{
+    builtins::{BuiltinFn, BuiltinType},
+    identifier::{CallTarget, FullIdentifier, Name},
+    operators::{BinOp, UnOp},
+    parser::ast::Expression,
+  },
+  report::location::WithLineInfo,
+};
+use std::{
+  cmp::Ordering,
+  collections::{HashMap, HashSet},
+};
+
+/// Value of an expression known at compile time
+#[derive(Debug, Clone, PartialEq)]
+pub enum Constant {
+  Bool(bool),
+  Int(i32),
+  // Fixed-point value, see FloatConfig
+  Float(i32),
+  String(String),
+  Array(Vec<Constant>),
+  // Fields in the order they are written
+  Struct(Vec<(Name, Constant)>),
+}
+
+/// Folds operations on literals, following the semantics of the commands they
+/// would compile to, and evaluates the initializers of globals when they only
+/// depend on literals and other constant globals
+pub struct ConstEvaluator {
+  // Fixed-point value of `1.0`, see FloatConfig
+  float_scale: i32,
+  // Folded values of the `const` declarations evaluated so far
+  constants: HashMap<FullIdentifier, ResolvedExpression>,
+  // Folded initializers of the other globals known at compile time
+  globals: HashMap<FullIdentifier, ResolvedExpression>,
+  // Whether globals known at compile time are replaced by their value, which
+  // only holds in initializers of globals since they may change at runtime
+  reads_globals: bool,
+}
+
+impl ConstEvaluator {
+  pub fn new(float_scale: i32) -> Self {
+    Self {
+      float_scale,
+      constants: HashMap::new(),
+      globals: HashMap::new(),
+      reads_globals: false,
+    }
+  }
+
+  /// Folds every function body, then every global initializer in the order
+  /// globals are initialized, which is recorded in the program along with
+  /// their values
+  pub fn evaluate(
+    mut program: Program,
+    float_scale: i32,
+  ) -> Result<Program, Box<SemanticError>> {
+    let order = initialization_order(&program)?;
+    let mut evaluator = ConstEvaluator::new(float_scale);
+    for id in &order {
+      let global = program.global_mut(id).unwrap();
+      evaluator.reads_globals = !global.constant;
+      global.val = evaluator.fold(&global.val);
+      global.value = evaluator.constant(&global.val);
+      let values = match (&global.value, global.constant) {
+        (Some(_), true) => &mut evaluator.constants,
+        (Some(_), false) => &mut evaluator.globals,
+        (None, true) => {
+          return Err(
+            SemanticError::new(
+              &global.module,
+              &format!("Constant `{id}` is not known at compile time"),
+              evaluator.culprit(&global.val),
+            )
+            .with_label("not a constant value")
+            .with_helper(
+              global.name.clone().map(|_| ()),
+              "constant declared here",
+            )
+            .with_note(
+              "Constants are made of literals, operators and other constants",
+            )
+            .into(),
+          )
+        }
+        (None, false) => continue,
+      };
+      values.insert(id.clone(), global.val.clone());
+    }
+
+    // Constants are inlined in function bodies
+    evaluator.reads_globals = false;
+    let functions: Vec<_> =
+      program.functions().map(|(id, _)| id.clone()).collect();
+    for id in functions {
+      let function = program.function_mut(&id).unwrap();
+      evaluator.statements(&mut function.body);
+    }
+    program.set_initialization_order(order);
+    Ok(program)
+  }
+
+  fn statements(&self, body: &mut [FnStatement]) {
+    for statement in body {
+      match statement {
+        FnStatement::VarDecl { val, .. } | FnStatement::SideEffect(val) => {
+          *val = self.fold(val)
+        }
+        // Indices in the target may read constants
+        FnStatement::Assignment { target, val, .. } => {
+          *target = self.fold(target);
+          *val = self.fold(val);
+        }
+        FnStatement::Return(val) => {
+          if let Some(val) = &mut val.value {
+            *val = self.fold(val);
+          }
+        }
+        FnStatement::If {
+          cond,
+          then,
+          otherwise,
+        } => {
+          *cond = self.fold(cond);
+          self.statements(then);
+          self.statements(otherwise);
+        }
+        FnStatement::Loop { cond, body, step } => {
+          if let Some(cond) = cond {
+            *cond = self.fold(cond);
+          }
+          self.statements(body);
+          self.statements(step);
+        }
+        FnStatement::Break | FnStatement::Continue => {}
+      }
+    }
+  }
+
+  /// Replaces the operations whose operands are known by their result
+  pub fn fold(&self, expr: &ResolvedExpression) -> ResolvedExpression {
+    let span = expr.span();
+    match expr {
+      Expression::AtomIdentifier(id) => {
+        let global = self.globals.get(&id.value).filter(|_| self.reads_globals);
+        let value = self.constants.get(&id.value).or(global);
+        value.cloned().unwrap_or(expr.clone())
+      }
+      Expression::Struct(id, fields) => Expression::Struct(
+        id.clone(),
+        fields
+          .iter()
+          .map(|(name, val)| (name.clone(), self.fold(val)))
+          .collect(),
+      ),
+      Expression::Array(span, elements) => Expression::Array(
+        span.clone(),
+        elements.iter().map(|element| self.fold(element)).collect(),
+      ),
+      Expression::Field(base, name) => {
+        let base = self.fold(base);
+        if let (Expression::Struct(_, fields), Some(_)) =
+          (&base, self.constant(&base))
+        {
+          if let Some((_, val)) =
+            fields.iter().find(|(field, _)| field.value == name.value)
+          {
+            return val.clone();
+          }
+        }
+        Expression::Field(Box::new(base), name.clone())
+      }
+      Expression::Index(base, index) => {
+        let base = self.fold(base);
+        let index = self.fold(index);
+        if let (Expression::Array(_, elements), Some(Constant::Int(index))) =
+          (&base, self.literal(&index))
+        {
+          // Negative indices count from the end, like in NBT paths
+          let position = if index < 0 {
+            elements.len().checked_sub(index.unsigned_abs() as usize)
+          } else {
+            Some(index as usize)
+          };
+          let element = position.and_then(|position| elements.get(position));
+          if let (Some(element), Some(_)) = (element, self.constant(&base)) {
+            return element.clone();
+          }
+        }
+        Expression::Index(Box::new(base), Box::new(index))
+      }
+      Expression::Call(target, args) => {
+        let args: Vec<_> = args.iter().map(|arg| self.fold(arg)).collect();
+        if let CallTarget::Builtin(BuiltinFn::Len) = target.value {
+          // Strings are measured in UTF-16 code units, like in the game
+          let len = match self.constant(&args[0]) {
+            Some(Constant::Array(elements)) => Some(elements.len()),
+            Some(Constant::String(value)) => Some(value.encode_utf16().count()),
+            _ => None,
+          };
+          if let Some(len) = len.and_then(|len| i32::try_from(len).ok()) {
+            return atom(Constant::Int(len), span, self.float_scale);
+          }
+        }
+        Expression::Call(target.clone(), args)
+      }
+      Expression::UnOp(op, operand) => {
+        let operand = self.fold(operand);
+        // The smallest integer is only written as a negated literal, whose
+        // operand does not fit in a scoreboard
+        if let (UnOp::Negate, Expression::AtomInteger(value)) =
+          (op.value, &operand)
+        {
+          if let Ok(value) = i32::try_from(-value.value) {
+            return atom(Constant::Int(value), span, self.float_scale);
+          }
+        }
+        let value = match (op.value, self.literal(&operand)) {
+          (UnOp::Negate, Some(Constant::Int(value))) => {
+            Some(Constant::Int(value.wrapping_neg()))
+          }
+          (UnOp::Negate, Some(Constant::Float(value))) => {
+            Some(Constant::Float(value.wrapping_neg()))
+          }
+          (UnOp::Not, Some(Constant::Bool(value))) => {
+            Some(Constant::Bool(!value))
+          }
+          (UnOp::BitNot, Some(Constant::Int(value))) => {
+            Some(Constant::Int(!value))
+          }
+          (UnOp::Identity, value) => value,
+          _ => None,
+        };
+        match value {
+          Some(value) => atom(value, span, self.float_scale),
+          None => Expression::UnOp(op.clone(), Box::new(operand)),
+        }
+      }
+      Expression::Cast(operand, typ) => {
+        let operand = self.fold(operand);
+        let value = match (self.literal(&operand), typ.value) {
+          (Some(Constant::Int(value)), BuiltinType::Float) => {
+            Some(Constant::Float(value.wrapping_mul(self.float_scale)))
+          }
+          (Some(Constant::Float(value)), BuiltinType::Int) => {
+            Some(Constant::Int(floor_div(value, self.float_scale)))
+          }
+          (value, _) => value,
+        };
+        match value {
+          Some(value) => atom(value, span, self.float_scale),
+          None => Expression::Cast(Box::new(operand), typ.clone()),
+        }
+      }
+      Expression::BinOp(left, op, right) => {
+        let left = self.fold(left);
+        let right = self.fold(right);
+        // A known left operand of `&&` or `||` either decides the result, so
+        // the right one never runs, or leaves it to the right one
+        if let (BinOp::And | BinOp::Or, Some(Constant::Bool(value))) =
+          (op.value, self.literal(&left))
+        {
+          return if value == (op.value == BinOp::Or) {
+            atom(Constant::Bool(value), span, self.float_scale)
+          } else {
+            right
+          };
+        }
+        let value = match (self.literal(&left), self.literal(&right)) {
+          (Some(lhs), Some(rhs)) => self.binary(op.value, lhs, rhs),
+          _ => None,
+        };
+        match value {
+          Some(value) => atom(value, span, self.float_scale),
+          None => {
+            Expression::BinOp(Box::new(left), op.clone(), Box::new(right))
+          }
+        }
+      }
+      Expression::AtomBoolean(_)
+      | Expression::AtomInteger(_)
+      | Expression::AtomFloat(_)
+      | Expression::AtomString(_) => expr.clone(),
+    }
+  }
+
+  /// Value of a folded expression made only of literals
+  pub fn constant(&self, expr: &ResolvedExpression) -> Option<Constant> {
+    match expr {
+      Expression::Array(_, elements) => elements
+        .iter()
+        .map(|element| self.constant(element))
+        .collect::<Option<_>>()
+        .map(Constant::Array),
+      Expression::Struct(_, fields) => fields
+        .iter()
+        .map(|(name, val)| Some((name.value.clone(), self.constant(val)?)))
+        .collect::<Option<_>>()
+        .map(Constant::Struct),
+      _ => self.literal(expr),
+    }
+  }
+
+  /// Part of a folded expression that keeps it from being constant
+  fn culprit(&self, expr: &ResolvedExpression) -> WithLineInfo<()> {
+    let operands = match expr {
+      Expression::Struct(_, fields) => {
+        fields.iter().map(|(_, val)| val).collect()
+      }
+      Expression::Array(_, elements) => elements.iter().collect(),
+      Expression::Field(base, _) => vec![base.as_ref()],
+      Expression::Index(base, index) => vec![base.as_ref(), index.as_ref()],
+      Expression::UnOp(_, operand) | Expression::Cast(operand, _) => {
+        vec![operand.as_ref()]
+      }
+      Expression::BinOp(left, _, right) => vec![left.as_ref(), right.as_ref()],
+      _ => Vec::new(),
+    };
+    operands
+      .into_iter()
+      .find(|operand| self.constant(operand).is_none())
+      .map_or_else(|| expr.span(), |operand| self.culprit(operand))
+  }
+
+  /// Value of a literal, unless it does not fit in a scoreboard
+  fn literal(&self, expr: &ResolvedExpression) -> Option<Constant> {
+    match expr {
+      Expression::AtomBoolean(value) => Some(Constant::Bool(value.value)),
+      Expression::AtomInteger(value) => {
+        i32::try_from(value.value).ok().map(Constant::Int)
+      }
+      Expression::AtomFloat(value) => {
+        let fixed = value.value * f64::from(self.float_scale);
+        (fixed.abs() <= f64::from(i32::MAX))
+          .then(|| Constant::Float(fixed.round() as i32))
+      }
+      Expression::AtomString(value) => {
+        Some(Constant::String(value.value.clone()))
+      }
+      _ => None,
+    }
+  }
+
+  /// Result of a binary operation, computed like scoreboard operations do.
+  /// Divisions by zero are left to fail at runtime.
+  fn binary(
+    &self,
+    op: BinOp,
+    lhs: Constant,
+    rhs: Constant,
+  ) -> Option<Constant> {
+    let scale = self.float_scale;
+    Some(match (lhs, rhs) {
+      (Constant::Int(a), Constant::Int(b)) => match op {
+        BinOp::Add => Constant::Int(a.wrapping_add(b)),
+        BinOp::Sub => Constant::Int(a.wrapping_sub(b)),
+        BinOp::Mul => Constant::Int(a.wrapping_mul(b)),
+        BinOp::Div | BinOp::Mod if b == 0 => return None,
+        BinOp::Div => Constant::Int(floor_div(a, b)),
+        BinOp::Mod => Constant::Int(floor_mod(a, b)),
+        BinOp::BitAnd => Constant::Int(a & b),
+        BinOp::BitOr => Constant::Int(a | b),
+        BinOp::BitXor => Constant::Int(a ^ b),
+        // Shift amounts are taken modulo 32, like the runtime helpers do
+        BinOp::ShiftLeft => Constant::Int(a.wrapping_shl(b as u32)),
+        BinOp::ShiftRight => Constant::Int(a.wrapping_shr(b as u32)),
+        _ => Constant::Bool(compare(op, a.cmp(&b))?),
+      },
+      (Constant::Float(a), Constant::Float(b)) => match op {
+        BinOp::Add => Constant::Float(a.wrapping_add(b)),
+        BinOp::Sub => Constant::Float(a.wrapping_sub(b)),
+        // Same steps as `fixed_mul` and `fixed_div` in the code generator
+        BinOp::Mul => {
+          let ra = floor_mod(a, scale);
+          let product = floor_div(a, scale)
+            .wrapping_mul(b)
+            .wrapping_add(floor_div(b, scale).wrapping_mul(ra))
+            .wrapping_add(floor_div(
+              floor_mod(b, scale).wrapping_mul(ra),
+              scale,
+            ));
+          Constant::Float(product)
+        }
+        BinOp::Div | BinOp::Mod if b == 0 => return None,
+        BinOp::Div => {
+          let remainder = floor_mod(a, b).wrapping_mul(scale);
+          let quotient = floor_div(a, b)
+            .wrapping_mul(scale)
+            .wrapping_add(floor_div(remainder, b));
+          Constant::Float(quotient)
+        }
+        BinOp::Mod => Constant::Float(floor_mod(a, b)),
+        _ => Constant::Bool(compare(op, a.cmp(&b))?),
+      },
+      (Constant::Bool(a), Constant::Bool(b)) => {
+        Constant::Bool(compare(op, a.cmp(&b)).filter(|_| is_equality(op))?)
+      }
+      (Constant::String(a), Constant::String(b)) => match op {
+        BinOp::Add => Constant::String(a + &b),
+        _ => {
+          Constant::Bool(compare(op, a.cmp(&b)).filter(|_| is_equality(op))?)
+        }
+      },
+      _ => return None,
+    })
+  }
+}
+
+/// Orders globals so that each one is initialized after the globals its
+/// initializer reads, directly or through the functions it calls
+fn initialization_order(
+  program: &Program,
+) -> Result<Vec<FullIdentifier>, Box<SemanticError>> {
+  let mut globals: Vec<_> =
+    program.globals().map(|(id, _)| id.clone()).collect();
+  globals.sort();
+  let mut order = Vec::new();
+  let mut done = HashSet::new();
+  for id in &globals {
+    visit(program, id, &mut Vec::new(), &mut done, &mut order)?;
+  }
+  Ok(order)
+}
+
+fn visit(
+  program: &Program,
+  id: &FullIdentifier,
+  path: &mut Vec<FullIdentifier>,
+  done: &mut HashSet<FullIdentifier>,
+  order: &mut Vec<FullIdentifier>,
+) -> Result<(), Box<SemanticError>> {
+  if done.contains(id) {
+    return Ok(());
+  }
+  if let Some(start) = path.iter().position(|step| step == id) {
+    return Err(cycle_error(program, &path[start..], id));
+  }
+
+  path.push(id.clone());
+  for (dependency, through) in reads(program, id) {
+    path.extend(through.iter().cloned());
+    visit(program, &dependency, path, done, order)?;
+    path.truncate(path.len() - through.len());
+  }
+  path.pop();
+  done.insert(id.clone());
+  order.push(id.clone());
+  Ok(())
+}
+
+/// Error for a global whose initializer reads itself through `path`, which
+/// starts with the global
+pub fn cycle_error(
+  program: &Program,
+  path: &[FullIdentifier],
+  id: &FullIdentifier,
+) -> Box<SemanticError> {
+  let global = program.global(id).unwrap();
+  let cycle: Vec<_> = path
+    .iter()
+    .chain([id])
+    .map(|step| format!("`{step}`"))
+    .collect();
+  SemanticError::new(
+    &global.module,
+    &format!("The initializer of `{id}` depends on itself"),
+    global.name.clone().map(|_| ()),
+  )
+  .with_label("initialized from its own value")
+  .with_note(&format!("Cycle: {}", cycle.join(" -> ")))
+  .into()
+}
+
+/// Globals read by the initializer of a global, each with the chain of
+/// functions it is read through
+fn reads(
+  program: &Program,
+  id: &FullIdentifier,
+) -> Vec<(FullIdentifier, Vec<FullIdentifier>)> {
+  let mut reads = Vec::new();
+  let mut visited = HashSet::new();
+  let mut worklist = vec![(id.clone(), Vec::new())];
+  while let Some((current, through)) = worklist.pop() {
+    let dependencies = match program.function(&current) {
+      Some(function) => &function.dependencies,
+      None if through.is_empty() && current == *id => {
+        &program.global(id).unwrap().dependencies
+      }
+      None => {
+        if program.global(&current).is_some() {
+          reads.push((current, through));
+        }
+        continue;
+      }
+    };
+    for dependency in dependencies.iter().rev() {
+      if dependency.global() && visited.insert(dependency.clone()) {
+        let mut through = through.clone();
+        if program.function(dependency).is_some() {
+          through.push(dependency.clone());
+        }
+        worklist.push((dependency.clone(), through));
+      }
+    }
+  }
+  reads
+}
+
+/// Literal holding a folded value, spanning the expression it replaces
+fn atom(
+  value: Constant,
+  span: WithLineInfo<()>,
+  float_scale: i32,
+) -> ResolvedExpression {
+  match value {
+    Constant::Bool(value) => Expression::AtomBoolean(span.map(|_| value)),
+    Constant::Int(value) => {
+      Expression::AtomInteger(span.map(|_| value as isize))
+    }
+    Constant::Float(value) => Expression::AtomFloat(
+      span.map(|_| f64::from(value) / f64::from(float_scale)),
+    ),
+    Constant::String(value) => Expression::AtomString(span.map(|_| value)),
+    Constant::Array(_) | Constant::Struct(_) => unreachable!(),
+  }
+}
+
+fn compare(op: BinOp, ordering: Ordering) -> Option<bool> {
+  Some(match op {
+    BinOp::Equal => ordering.is_eq(),
+    BinOp::NotEqual => ordering.is_ne(),
+    BinOp::Less => ordering.is_lt(),
+    BinOp::LessOrEqual => ordering.is_le(),
+    BinOp::Greater => ordering.is_gt(),
+    BinOp::GreaterOrEqual => ordering.is_ge(),
+    _ => return None,
+  })
+}
+
+fn is_equality(op: BinOp) -> bool {
+  matches!(op, BinOp::Equal | BinOp::NotEqual)
+}
+
+/// Division rounding towards negative infinity, like `/=` on scores
+fn floor_div(a: i32, b: i32) -> i32 {
+  let quotient = a.wrapping_div(b);
+  if a.wrapping_rem(b) != 0 && (a < 0) != (b < 0) {
+    quotient - 1
+  } else {
+    quotient
+  }
+}
+
+/// Remainder with the sign of the divisor, like `%=` on scores
+fn floor_mod(a: i32, b: i32) -> i32 {
+  a.wrapping_sub(floor_div(a, b).wrapping_mul(b))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::Constant;
+  use crate::{
+    grammar::identifier::FullIdentifier,
+    testing::{analyze, error},
+  };
+
+  /// Folded value of the constant `X` declared in `source`
+  fn value(source: &str) -> Constant {
+    let (program, _) = analyze(source).unwrap();
+    let id = FullIdentifier::Global(vec![String::from("X")]);
+    program.global(&id).unwrap().value.clone().unwrap()
+  }
+
+  fn int(expression: &str) -> i32 {
+    match value(&format!("const X: int = {expression};")) {
+      Constant::Int(value) => value,
+      value => panic!("expected an int, found {value:?}"),
+    }
+  }
+
+  fn float(expression: &str) -> i32 {
+    match value(&format!("const X: float = {expression};")) {
+      Constant::Float(value) => value,
+      value => panic!("expected a float, found {value:?}"),
+    }
+  }
+
+  #[test]
+  fn arithmetic_wraps() {
+    assert_eq!(int("2147483647 + 1"), i32::MIN);
+    assert_eq!(int("-2147483648 - 1"), i32::MAX);
+    assert_eq!(int("65536 * 65536"), 0);
+    assert_eq!(int("-(-2147483648)"), i32::MIN);
+  }
+
+  #[test]
+  fn division_rounds_down() {
+    assert_eq!(int("7 / 2"), 3);
+    assert_eq!(int("-7 / 2"), -4);
+    assert_eq!(int("7 / -2"), -4);
+    assert_eq!(int("-7 / -2"), 3);
+  }
+
+  #[test]
+  fn remainder_has_the_sign_of_the_divisor() {
+    assert_eq!(int("7 % 2"), 1);
+    assert_eq!(int("-7 % 2"), 1);
+    assert_eq!(int("7 % -2"), -1);
+    assert_eq!(int("-7 % -2"), -1);
+  }
+
+  #[test]
+  fn shifts_are_taken_modulo_32() {
+    assert_eq!(int("1 << 33"), 2);
+    assert_eq!(int("-8 >> 1"), -4);
+    assert_eq!(int("~5 & 0xff"), 250);
+  }
+
+  #[test]
+  fn fixed_point_products_are_rescaled() {
+    assert_eq!(float("1.5 * 2.25"), 3375);
+    assert_eq!(float("-1.5 * 1.5"), -2250);
+    assert_eq!(float("7.0 / 2.0"), 3500);
+    assert_eq!(float("1.0 / 3.0"), 333);
+    assert_eq!(float("-1.0 / 3.0"), -334);
+  }
+
+  #[test]
+  fn smallest_int_is_a_negated_literal() {
+    assert_eq!(int("-2147483648"), i32::MIN);
+  }
+
+  #[test]
+  fn int_literal_out_of_range() {
+    for source in [
+      "fn main() { let a = 2147483648; }",
+      "fn main() { let a = -2147483649; }",
+      "fn main() { let a = 18446744073709551615; }",
+      "let a = 1 - 2147483648;",
+    ] {
+      let err = error(source);
+      assert_eq!(err.message, "Integer literal does not fit in a scoreboard");
+      assert_eq!(err.focus.value.as_deref(), Some("out of range"));
+    }
+  }
+
+  #[test]
+  fn division_by_zero_is_left_to_runtime() {
+    let err = error("const X: int = 1 / 0;");
+    assert_eq!(err.message, "Constant `X` is not known at compile time");
+  }
+
+  #[test]
+  fn constants_read_other_constants() {
+    let source = "const A: int = 6;\nconst X: int = A * 7;";
+    assert_eq!(value(source), Constant::Int(42));
+  }
+
+  #[test]
+  fn constants_cannot_read_variables() {
+    let err = error("let mut g = 1;\nconst X: int = g;");
+    assert_eq!(err.message, "Constant `X` is not known at compile time");
+    assert_eq!(err.helpers[0].value, "constant declared here");
+    assert_eq!(
+      err.notes,
+      ["Constants are made of literals, operators and other constants"]
+    );
+  }
+}
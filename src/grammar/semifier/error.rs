@@ -0,0 +1,174 @@
+use std::path::Path;
+
+use crate::report::{
+  location::WithLineInfo,
+  message::{
+    highlight::HighlightType,
+    line::{HighlightedLine, LineType},
+    Message, MessageMeta, MessageType,
+  },
+};
+
+use super::module::ModulePath;
+
+#[derive(Debug, Clone)]
+pub struct SemanticError {
+  pub module: ModulePath,
+  pub message: String,
+  pub focus: WithLineInfo<Option<String>>,
+  pub helpers: Vec<WithLineInfo<String>>,
+  pub notes: Vec<String>,
+  pub suggestion: Option<Suggestion>,
+}
+
+/// Text to insert in the source to fix an error, shown in a help message
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+  pub message: String,
+  pub insertion: WithLineInfo<String>,
+}
+
+impl Suggestion {
+  /// Line of `source` the insertion goes to, with the insertion made
+  pub fn apply(&self, source: &str) -> Option<String> {
+    let insertion = &self.insertion;
+    let mut line = String::from(source.lines().nth(insertion.line - 1)?);
+    let offset = line
+      .char_indices()
+      .nth(insertion.column)
+      .map_or(line.len(), |(offset, _)| offset);
+    line.insert_str(offset, &insertion.value);
+    Some(line)
+  }
+}
+
+impl SemanticError {
+  pub fn new(
+    module: &ModulePath,
+    message: &str,
+    focus: WithLineInfo<()>,
+  ) -> Self {
+    Self {
+      module: module.clone(),
+      message: String::from(message),
+      focus: focus.map(|_| None),
+      helpers: Vec::new(),
+      notes: Vec::new(),
+      suggestion: None,
+    }
+  }
+
+  pub fn with_label(mut self, label: &str) -> Self {
+    self.focus.value = Some(String::from(label));
+    self
+  }
+
+  pub fn with_helper(mut self, span: WithLineInfo<()>, label: &str) -> Self {
+    self.helpers.push(span.map(|_| String::from(label)));
+    self
+  }
+
+  pub fn with_note(mut self, note: &str) -> Self {
+    self.notes.push(String::from(note));
+    self
+  }
+
+  /// Suggests inserting `text` before `at`, highlighting it without its
+  /// trailing spaces
+  pub fn with_suggestion(
+    mut self,
+    message: &str,
+    at: WithLineInfo<()>,
+    text: &str,
+  ) -> Self {
+    self.suggestion = Some(Suggestion {
+      message: String::from(message),
+      insertion: WithLineInfo {
+        value: String::from(text),
+        line: at.line,
+        column: at.column,
+        len: text.trim_end().chars().count(),
+      },
+    });
+    self
+  }
+
+  pub fn get_report(&self, path: &Path, source: &str) -> Message {
+    self.get_message(path, source, MessageType::Error)
+  }
+
+  /// Same as `get_report`, for diagnostics that do not stop compilation
+  pub fn get_warning(&self, path: &Path, source: &str) -> Message {
+    self.get_message(path, source, MessageType::Warning)
+  }
+
+  /// Help message showing the suggestion, if any, applied to its line
+  pub fn get_help(&self, path: &Path, source: &str) -> Option<Message> {
+    let suggestion = self.suggestion.as_ref()?;
+    let insertion = &suggestion.insertion;
+    let line = HighlightedLine::new(
+      insertion.line,
+      &suggestion.apply(source)?,
+      LineType::Suggestion,
+    )
+    .with_highlight(insertion.make_highlight(HighlightType::Suggestion, None));
+    let message = Message::new(&suggestion.message, MessageType::Help)
+      .with_meta(MessageMeta::FileLocation(
+        path.to_path_buf(),
+        insertion.line,
+        insertion.column,
+      ))
+      .with_line(line);
+    Some(message)
+  }
+
+  fn get_message(
+    &self,
+    path: &Path,
+    source: &str,
+    typ: MessageType,
+  ) -> Message {
+    let mut highlights = vec![(
+      self.focus.line,
+      self
+        .focus
+        .make_highlight(HighlightType::Focus, self.focus.value.as_deref()),
+    )];
+    highlights.extend(self.helpers.iter().map(|helper| {
+      (
+        helper.line,
+        helper.make_highlight(HighlightType::Helper, Some(&helper.value)),
+      )
+    }));
+    highlights.sort_by_key(|(line, _)| *line);
+
+    let mut lines: Vec<HighlightedLine> = Vec::new();
+    let mut current = None;
+    for (num, highlight) in highlights {
+      if current != Some(num) {
+        if let Some(line) = source.lines().nth(num - 1) {
+          lines.push(HighlightedLine::new(num, line, LineType::Source));
+          current = Some(num);
+        } else {
+          continue;
+        }
+      }
+      let line = lines.pop().unwrap();
+      lines.push(line.with_highlight(highlight));
+    }
+
+    let message =
+      Message::new(&self.message, typ).with_meta(MessageMeta::FileLocation(
+        path.to_path_buf(),
+        self.focus.line,
+        self.focus.column,
+      ));
+    let message = lines
+      .into_iter()
+      .fold(message, |message, line| message.with_line(line));
+    self
+      .notes
+      .iter()
+      .fold(message, |message, note| message.with_note(note))
+  }
+}
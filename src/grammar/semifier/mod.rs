@@ -1,61 +1,172 @@
 pub mod ast;
+pub mod consteval;
+pub mod error;
 pub mod module;
 pub mod program;
 pub mod resolver;
+pub mod typeck;
 
-use crate::pipeline::Tree;
+use crate::{pipeline::Tree, report::message::ExitBehavior};
+use consteval::ConstEvaluator;
+use error::SemanticError;
 use module::ModulePath;
 use program::Program;
-use resolver::NameResolver;
-use std::collections::{HashMap, HashSet};
+use resolver::{Item, ItemKind, Items, NameResolver};
+use std::collections::HashMap;
+use typeck::TypeChecker;
 
 use super::{identifier::FullIdentifier, parser::ast::Node};
 
 // Made up name to mean "semantic analysis doer & minimizer"
-pub struct Semifier;
+pub struct Semifier {
+  pub warn_unused: bool,
+  // Fixed-point value of `1.0`, see FloatConfig
+  pub float_scale: i32,
+}
 
 impl Semifier {
   pub fn semify(&self, modules: HashMap<ModulePath, Tree>) -> Program {
-    let mut program = Program::new();
-    let mut dependencies = HashSet::new();
+    let (mut program, warnings) = self
+      .analyze(&modules)
+      .unwrap_or_else(|err| report(&modules, &err));
+    for warning in warnings {
+      let tree = &modules[&warning.module];
+      warning
+        .get_warning(&tree.path, &tree.source)
+        .report(ExitBehavior::OnlyReport);
+    }
+
+    let mut unused = program.shake();
+    if self.warn_unused {
+      unused.sort_by(|a, b| {
+        (&a.module, &a.name.value).cmp(&(&b.module, &b.name.value))
+      });
+      for function in unused {
+        let tree = &modules[&function.module];
+        SemanticError::new(
+          &function.module,
+          &format!("Function `{}` is never used", function.name.value),
+          function.name.map(|_| ()),
+        )
+        .with_note("It is not reachable from any exported function")
+        .get_warning(&tree.path, &tree.source)
+        .report(ExitBehavior::OnlyReport);
+      }
+    }
+    program
+  }
+
+  /// Resolves, checks and folds every module, stopping at the first error.
+  /// Also returns warnings about code that compiles but may not behave as
+  /// written.
+  pub fn analyze(
+    &self,
+    modules: &HashMap<ModulePath, Tree>,
+  ) -> Result<(Program, Vec<SemanticError>), Box<SemanticError>> {
+    let mut order: Vec<_> = modules.keys().collect();
+    order.sort();
+
+    // First pass register the names declared by every module
+    let mut items = Items::new();
+    for module in &order {
+      for node in &modules[module].nodes {
+        let (name, kind) = match node {
+          Node::FnDecl { name, .. } => (name, ItemKind::Function),
+          Node::VarDecl { typ, mutable, .. } => {
+            (&typ.name, ItemKind::Variable { mutable: *mutable })
+          }
+          Node::ConstDecl { name, .. } => (name, ItemKind::Constant),
+          Node::StructDecl { name, .. } => (name, ItemKind::Struct),
+          _ => continue,
+        };
+        let path = FullIdentifier::compose_global(module, &name.value);
+        if let Some(previous) = items.get(&path) {
+          return Err(
+            SemanticError::new(
+              module,
+              &format!("`{}` is defined multiple times", name.value),
+              name.clone().map(|_| ()),
+            )
+            .with_label("redefined here")
+            .with_helper(
+              previous.name.clone().map(|_| ()),
+              "first defined here",
+            )
+            .into(),
+          );
+        }
+        let name = name.clone();
+        items.insert(path, Item { kind, name });
+      }
+    }
 
-    // First pass load all independent functions
-    for (module, tree) in modules {
-      let mut resolver = NameResolver::new(module.clone());
-      // First pass in each function register names in the resolver
+    // Second pass resolve every declaration
+    let mut program = Program::new();
+    for module in order {
+      let tree = &modules[module];
+      let mut resolver = NameResolver::new(module.clone(), &items);
       for node in &tree.nodes {
-        // TODO:
-        // match node {
-        //   Node::FnDecl { name, .. } => resolver.use_name(relpath)
-        // }
+        if let Node::UseDecl(id) = node {
+          resolver.use_name(id)?;
+        }
       }
       for node in &tree.nodes {
         match node {
-          Node::FnDecl {
-            attributes, name, ..
-          } => {
-            for attr in attributes {
-              if attr.value.independent() {
-                let full_path =
-                  FullIdentifier::compose_global(&module, &name.value);
-                let deps =
-                  program.load_function(full_path, &mut resolver, node.clone());
-                for dep in deps.into_iter().filter(|dep| dep.global()) {
-                  dependencies.insert(dep);
-                }
-              }
-            }
+          Node::FnDecl { name, .. } => {
+            let full_path = FullIdentifier::compose_global(module, &name.value);
+            program.load_function(full_path, &mut resolver, node.clone())?;
+          }
+          Node::VarDecl { .. } | Node::ConstDecl { .. } => {
+            program.load_global(module.clone(), &resolver, node.clone())?
+          }
+          Node::StructDecl { .. } => {
+            program.load_struct(module.clone(), &resolver, node.clone())?
           }
           _ => {}
-        }
+        };
       }
     }
 
-    // Second pass load all dependencies
-    for dep in dependencies {
+    let (program, warnings) = TypeChecker::check(program, self.float_scale)?;
+    let program = ConstEvaluator::evaluate(program, self.float_scale)?;
+    Ok((program, warnings))
+  }
+}
 
+fn report(modules: &HashMap<ModulePath, Tree>, err: &SemanticError) -> ! {
+  let tree = &modules[&err.module];
+  let error = err.get_report(&tree.path, &tree.source);
+  // A help fixing the error is shown after it, before stopping
+  match err.get_help(&tree.path, &tree.source) {
+    Some(help) => {
+      error.report(ExitBehavior::OnlyReport);
+      help.report_and_exit(1)
     }
+    None => error.report_and_exit(1),
+  }
+}
 
-    program
+#[cfg(test)]
+mod tests {
+  use crate::testing::{analyze, error};
+
+  #[test]
+  fn items_are_known_before_their_declaration() {
+    let source = "fn main() -> int { return f() + N; }
+fn f() -> int { return 1; }
+const N: int = 2;";
+    assert!(analyze(source).is_ok());
+  }
+
+  #[test]
+  fn item_defined_twice() {
+    let err = error(
+      "fn f() {}
+let f = 1;",
+    );
+    assert_eq!(err.message, "`f` is defined multiple times");
+    assert_eq!(err.focus.line, 2);
+    assert_eq!(err.helpers[0].value, "first defined here");
+    assert_eq!(err.helpers[0].line, 1);
   }
 }
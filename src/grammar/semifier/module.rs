@@ -1,17 +1,18 @@
-use std::path::PathBuf;
+use std::{fmt, path::PathBuf};
 
 use crate::{
   grammar::identifier::{Identifier, Name},
   report::message::Message,
 };
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ModulePath(pub Vec<Name>);
 
 impl ModulePath {
   pub fn main() -> Self {
     ModulePath(vec![])
   }
+  #[allow(dead_code)]
   pub fn of_root_identifier(id: &Identifier) -> Self {
     if id.root {
       let mut parts: Vec<_> =
@@ -27,14 +28,14 @@ impl ModulePath {
   }
 
   pub fn paths(&self, root: PathBuf) -> Vec<PathBuf> {
-    if self.0.len() == 0 {
+    if self.0.is_empty() {
       vec![root.join("lib.lc")]
     } else {
       let names_except_last = self.0[..self.0.len() - 1].iter();
       let last_name = self.0.last().unwrap();
       let root = names_except_last.fold(root, |acc, name| acc.join(name));
       vec![
-        root.join(&format!("{last_name}.lc")),
+        root.join(format!("{last_name}.lc")),
         root.join(last_name).join("mod.lc"),
       ]
     }
@@ -47,17 +48,12 @@ impl ModulePath {
   }
 }
 
-impl ToString for ModulePath {
-  fn to_string(&self) -> String {
-    if self.0.len() == 0 {
-      "main".to_string()
+impl fmt::Display for ModulePath {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    if self.0.is_empty() {
+      write!(f, "main")
     } else {
-      self
-        .0
-        .iter()
-        .map(|name| name.to_string())
-        .collect::<Vec<_>>()
-        .join("::")
+      write!(f, "{}", self.0.join("::"))
     }
   }
 }
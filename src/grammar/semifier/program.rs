@@ -1,16 +1,28 @@
-use super::{ast::FnDecl, resolver::NameResolver};
+use super::{
+  ast::{FnDecl, GlobalDecl, StructDecl},
+  error::SemanticError,
+  module::ModulePath,
+  resolver::NameResolver,
+};
 use crate::grammar::{identifier::FullIdentifier, parser::ast::Node};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone)]
 pub struct Program {
   functions: HashMap<FullIdentifier, FnDecl>,
+  globals: HashMap<FullIdentifier, GlobalDecl>,
+  structs: HashMap<FullIdentifier, StructDecl>,
+  // Globals in the order their initializers run
+  initialization: Vec<FullIdentifier>,
 }
 
 impl Program {
   pub fn new() -> Self {
     Program {
       functions: HashMap::new(),
+      globals: HashMap::new(),
+      structs: HashMap::new(),
+      initialization: Vec::new(),
     }
   }
 
@@ -20,10 +32,181 @@ impl Program {
     path: FullIdentifier,
     resolver: &mut NameResolver,
     fnnode: Node,
-  ) -> Vec<FullIdentifier> {
-    let function = FnDecl::from_function_node(fnnode, resolver);
+  ) -> Result<Vec<FullIdentifier>, Box<SemanticError>> {
+    let function =
+      FnDecl::from_function_node(path.module_path(), fnnode, resolver)?;
     let deps = function.dependencies.clone();
     self.functions.insert(path, function);
-    deps
+    Ok(deps)
+  }
+
+  pub fn load_global(
+    &mut self,
+    module: ModulePath,
+    resolver: &NameResolver,
+    varnode: Node,
+  ) -> Result<(), Box<SemanticError>> {
+    let global = GlobalDecl::from_var_node(module, varnode, resolver)?;
+    let path =
+      FullIdentifier::compose_global(&global.module, &global.name.value);
+    self.globals.insert(path, global);
+    Ok(())
+  }
+
+  pub fn load_struct(
+    &mut self,
+    module: ModulePath,
+    resolver: &NameResolver,
+    structnode: Node,
+  ) -> Result<(), Box<SemanticError>> {
+    let decl = StructDecl::from_struct_node(module, structnode, resolver)?;
+    let path = FullIdentifier::compose_global(&decl.module, &decl.name.value);
+    self.structs.insert(path, decl);
+    Ok(())
+  }
+
+  /// Drops every function and global that exported functions cannot reach,
+  /// returning the functions dropped
+  pub fn shake(&mut self) -> Vec<FnDecl> {
+    let mut worklist: Vec<_> = self
+      .functions
+      .iter()
+      .filter(|(_, decl)| decl.independent())
+      .map(|(path, _)| path.clone())
+      .collect();
+    let mut reachable = HashSet::new();
+    while let Some(path) = worklist.pop() {
+      if !reachable.insert(path.clone()) {
+        continue;
+      }
+      let dependencies = match self.functions.get(&path) {
+        Some(function) => &function.dependencies,
+        None => match self.globals.get(&path) {
+          Some(global) => &global.dependencies,
+          None => continue,
+        },
+      };
+      worklist.extend(dependencies.iter().filter(|dep| dep.global()).cloned());
+    }
+
+    self.globals.retain(|path, _| reachable.contains(path));
+    let (kept, dropped) = self
+      .functions
+      .drain()
+      .partition(|(path, _)| reachable.contains(path));
+    self.functions = kept;
+    dropped.into_values().collect()
+  }
+
+  pub fn function(&self, path: &FullIdentifier) -> Option<&FnDecl> {
+    self.functions.get(path)
+  }
+
+  pub fn function_mut(&mut self, path: &FullIdentifier) -> Option<&mut FnDecl> {
+    self.functions.get_mut(path)
+  }
+
+  pub fn functions(
+    &self,
+  ) -> impl Iterator<Item = (&FullIdentifier, &FnDecl)> + '_ {
+    self.functions.iter()
+  }
+
+  pub fn global(&self, path: &FullIdentifier) -> Option<&GlobalDecl> {
+    self.globals.get(path)
+  }
+
+  pub fn global_mut(
+    &mut self,
+    path: &FullIdentifier,
+  ) -> Option<&mut GlobalDecl> {
+    self.globals.get_mut(path)
+  }
+
+  pub fn globals(
+    &self,
+  ) -> impl Iterator<Item = (&FullIdentifier, &GlobalDecl)> + '_ {
+    self.globals.iter()
+  }
+
+  pub fn set_initialization_order(&mut self, order: Vec<FullIdentifier>) {
+    self.initialization = order;
+  }
+
+  /// Globals in the order their initializers must run, each after the
+  /// globals it reads
+  pub fn initialization_order(
+    &self,
+  ) -> impl Iterator<Item = (&FullIdentifier, &GlobalDecl)> + '_ {
+    self
+      .initialization
+      .iter()
+      .filter_map(|id| Some((id, self.globals.get(id)?)))
+  }
+
+  pub fn structure(&self, path: &FullIdentifier) -> Option<&StructDecl> {
+    self.structs.get(path)
+  }
+
+  pub fn structs(
+    &self,
+  ) -> impl Iterator<Item = (&FullIdentifier, &StructDecl)> + '_ {
+    self.structs.iter()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{grammar::identifier::FullIdentifier, testing::analyze};
+
+  /// Names of the functions `shake` drops from `source`, sorted
+  fn dropped(source: &str) -> Vec<String> {
+    let (mut program, _) = analyze(source).unwrap();
+    let mut names: Vec<_> = program
+      .shake()
+      .into_iter()
+      .map(|function| function.name.value)
+      .collect();
+    names.sort();
+    names
+  }
+
+  #[test]
+  fn unreachable_functions_are_dropped() {
+    let source = "fn unused() { helper(); }
+fn helper() {}
+fn used() {}
+#[export]
+fn main() { used(); }";
+    assert_eq!(dropped(source), ["helper", "unused"]);
+  }
+
+  #[test]
+  fn functions_reached_from_nested_blocks_are_kept() {
+    let source = "fn f() -> bool { return true; }
+fn g() {}
+#[export]
+fn main() {
+  while f() {
+    if true {
+      g();
+    }
+  }
+}";
+    assert!(dropped(source).is_empty());
+  }
+
+  #[test]
+  fn globals_read_by_kept_code_are_kept() {
+    let source = "fn init() -> int { return 1; }
+let read = init();
+let unread = init();
+#[export]
+fn main() -> int { return read; }";
+    let (mut program, _) = analyze(source).unwrap();
+    assert!(program.shake().is_empty());
+    let global = |name: &str| FullIdentifier::Global(vec![name.to_string()]);
+    assert!(program.global(&global("read")).is_some());
+    assert!(program.global(&global("unread")).is_none());
   }
 }
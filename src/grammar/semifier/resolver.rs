@@ -4,13 +4,63 @@ use crate::{
 };
 use std::collections::HashMap;
 
-use super::module::ModulePath;
+use super::{error::SemanticError, module::ModulePath};
+
+/// Names declared at the top level of every module of the program
+pub type Items = HashMap<FullIdentifier, Item>;
+
+#[derive(Debug, Clone)]
+pub struct Item {
+  pub kind: ItemKind,
+  pub name: WithLineInfo<Name>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ItemKind {
+  Function,
+  Variable { mutable: bool },
+  Constant,
+  Struct,
+}
 
 #[derive(Debug, Clone)]
-pub struct NameResolver {
+pub struct NameResolver<'a> {
   current_path: ModulePath,
+  items: &'a Items,
   aliases: HashMap<Name, ResolvedName>,
-  scopes: Vec<Vec<(Name, bool)>>,
+  scopes: Vec<Scope>,
+  // Number of locals declared so far in the current function, per name
+  declared: HashMap<Name, usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+  Function,
+  Block,
+  // Body of a loop, which `break` and `continue` apply to
+  Loop,
+}
+
+#[derive(Debug, Clone)]
+struct Scope {
+  kind: ScopeKind,
+  locals: Vec<Local>,
+}
+
+/// How a local variable was introduced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+  Let { mutable: bool },
+  Param,
+  // Variable of a `for` loop
+  Loop,
+}
+
+#[derive(Debug, Clone)]
+struct Local {
+  name: WithLineInfo<Name>,
+  slot: Name,
+  binding: Binding,
 }
 
 #[derive(Debug, Clone)]
@@ -19,71 +69,453 @@ pub struct ResolvedName {
   pub mutable: bool,
 }
 
-impl NameResolver {
-  pub fn new(self_path: ModulePath) -> Self {
+impl<'a> NameResolver<'a> {
+  pub fn new(self_path: ModulePath, items: &'a Items) -> Self {
     NameResolver {
       current_path: self_path,
+      items,
       aliases: HashMap::new(),
       scopes: vec![],
+      declared: HashMap::new(),
     }
   }
 
-  pub fn use_name(&mut self, relpath: Identifier, mutable: bool) {
-    let full_path = relpath.full_path(self.current_path.clone());
-    let name = relpath.name();
-    self.aliases.insert(
-      name,
-      ResolvedName {
-        id: full_path,
-        mutable,
-      },
-    );
+  pub fn module(&self) -> &ModulePath {
+    &self.current_path
   }
-  pub fn decl_global(&mut self, name: WithLineInfo<Name>, mutable: bool) {
-    self.use_name(Identifier::from_name(name), mutable)
+
+  pub fn use_name(
+    &mut self,
+    relpath: &Identifier,
+  ) -> Result<(), Box<SemanticError>> {
+    let resolved = self.resolve_path(relpath)?;
+    self.aliases.insert(relpath.name(), resolved);
+    Ok(())
   }
-  pub fn decl_local(&mut self, name: Name, mutable: bool) {
-    self.scopes.last_mut().unwrap().push((name, mutable));
+  /// Declares a local variable in the innermost scope, returning the name
+  /// of its slot in the stack frame. Variables shadowing another one of the
+  /// same function get a slot of their own.
+  pub fn decl_local(
+    &mut self,
+    name: &WithLineInfo<Name>,
+    binding: Binding,
+  ) -> Name {
+    let count = self.declared.entry(name.value.clone()).or_insert(0);
+    let slot = match count {
+      0 => name.value.clone(),
+      _ => format!("{}-{count}", name.value),
+    };
+    *count += 1;
+    self.scopes.last_mut().unwrap().locals.push(Local {
+      name: name.clone(),
+      slot: slot.clone(),
+      binding,
+    });
+    slot
   }
 
-  pub fn push_scope(&mut self) {
-    self.scopes.push(Vec::new());
+  pub fn push_scope(&mut self, kind: ScopeKind) {
+    if kind == ScopeKind::Function {
+      self.declared.clear();
+    }
+    self.scopes.push(Scope {
+      kind,
+      locals: Vec::new(),
+    });
   }
   pub fn pop_scope(&mut self) {
     self.scopes.pop();
   }
 
-  pub fn get_local(&self, name: &Name) -> Option<ResolvedName> {
+  /// Checks that a `break` or `continue` is inside a loop
+  pub fn jump(
+    &self,
+    keyword: &str,
+    span: WithLineInfo<()>,
+  ) -> Result<(), Box<SemanticError>> {
+    if self
+      .scopes
+      .iter()
+      .any(|scope| scope.kind == ScopeKind::Loop)
+    {
+      return Ok(());
+    }
+    Err(
+      SemanticError::new(
+        &self.current_path,
+        &format!("`{keyword}` outside of a loop"),
+        span,
+      )
+      .with_label(&format!("cannot `{keyword}` here"))
+      .into(),
+    )
+  }
+
+  fn find_local(&self, name: &Name) -> Option<&Local> {
     self
       .scopes
       .iter()
       .rev()
       .filter_map(|scope| {
-        scope.iter().find(|(local_name, _)| local_name == name)
+        // Later declarations shadow earlier ones of the same scope
+        scope
+          .locals
+          .iter()
+          .rev()
+          .find(|local| local.name.value == *name)
       })
       .next()
-      .map(|(name, mutable)| ResolvedName {
-        id: FullIdentifier::Local(name.clone()),
-        mutable: *mutable,
-      })
   }
 
-  pub fn resolve(&self, id: &Identifier) -> ResolvedName {
-    if id.root {
-      let mut path = self.current_path.0.clone();
-      path.extend(id.parts.iter().map(|part| part.value.clone()));
-      return ResolvedName {
-        id: FullIdentifier::Global(path),
-        mutable: false,
-      };     
+  pub fn get_local(&self, name: &Name) -> Option<ResolvedName> {
+    self.find_local(name).map(|local| ResolvedName {
+      id: FullIdentifier::Local(local.slot.clone()),
+      mutable: local.binding == Binding::Let { mutable: true },
+    })
+  }
+
+  /// Resolves the name of a type, which must be a struct
+  pub fn resolve_type(
+    &self,
+    id: &Identifier,
+  ) -> Result<FullIdentifier, Box<SemanticError>> {
+    let resolved = self.resolve(id)?;
+    match self.items.get(&resolved.id) {
+      Some(Item {
+        kind: ItemKind::Struct,
+        ..
+      }) => Ok(resolved.id),
+      _ => Err(
+        SemanticError::new(
+          &self.current_path,
+          &format!("`{}` is not a type", id.name()),
+          id.span(()),
+        )
+        .with_label("not a type")
+        .into(),
+      ),
+    }
+  }
+
+  /// Resolves the target of an assignment, which must be a mutable variable
+  pub fn resolve_target(
+    &self,
+    id: &Identifier,
+  ) -> Result<ResolvedName, Box<SemanticError>> {
+    let resolved = self.resolve(id)?;
+    if resolved.mutable {
+      return Ok(resolved);
     }
+    let name = id.name();
+    let mut err = SemanticError::new(
+      &self.current_path,
+      &format!("Cannot assign to immutable variable `{name}`"),
+      id.span(()),
+    )
+    .with_label("cannot assign");
+    match &resolved.id {
+      FullIdentifier::Local(_) => {
+        let local = self.find_local(&name).unwrap();
+        err = match local.binding {
+          Binding::Let { .. } => err
+            .with_helper(local.name.clone().map(|_| ()), "declared here")
+            .with_suggestion(
+              "Consider making the variable mutable",
+              local.name.clone().map(|_| ()),
+              "mut ",
+            ),
+          Binding::Param | Binding::Loop => err
+            .with_helper(local.name.clone().map(|_| ()), "declared here")
+            .with_note(&format!(
+              "Consider assigning to a mutable copy: `let mut {name} = {name};`"
+            )),
+        };
+      }
+      FullIdentifier::Global(_) => {
+        // Functions and structs are left to the type checker
+        let item = &self.items[&resolved.id];
+        if item.kind == ItemKind::Constant {
+          err = SemanticError::new(
+            &self.current_path,
+            &format!("Cannot assign to constant `{name}`"),
+            id.span(()),
+          )
+          .with_label("cannot assign")
+          .with_note(
+            "Constants are replaced by their value where they are used",
+          );
+        } else if !matches!(item.kind, ItemKind::Variable { .. }) {
+          return Ok(resolved);
+        }
+        // Declarations of other modules are in another file, so the fix is
+        // only shown on the declaration in this one
+        let module = resolved.id.module_path();
+        let mutable = item.kind != ItemKind::Constant;
+        let declaration = item.name.clone().map(|_| ());
+        err = if module == self.current_path {
+          err = err.with_helper(declaration.clone(), "declared here");
+          if mutable {
+            err.with_suggestion(
+              "Consider making the variable mutable",
+              declaration,
+              "mut ",
+            )
+          } else {
+            err
+          }
+        } else {
+          err = err.with_note(&format!(
+            "`{}` is declared in module `{module}`",
+            item.name.value
+          ));
+          if mutable {
+            err.with_note(&format!(
+              "Consider making the variable mutable: `let mut {}`",
+              item.name.value
+            ))
+          } else {
+            err
+          }
+        };
+      }
+    }
+    Err(err.into())
+  }
+
+  pub fn resolve(
+    &self,
+    id: &Identifier,
+  ) -> Result<ResolvedName, Box<SemanticError>> {
     if id.is_singular() {
-      if let Some(alias) = self.get_local(&id.name()) {
-        return alias;
+      if let Some(local) = self.get_local(&id.name()) {
+        return Ok(local);
+      }
+      if let Some(alias) = self.aliases.get(&id.name()) {
+        return Ok(alias.clone());
       }
     }
+    self.resolve_path(id)
+  }
 
+  fn resolve_path(
+    &self,
+    id: &Identifier,
+  ) -> Result<ResolvedName, Box<SemanticError>> {
+    let full_path = id.full_path(self.current_path.clone());
+    match self.items.get(&full_path) {
+      Some(item) => Ok(ResolvedName {
+        id: full_path,
+        mutable: item.kind == ItemKind::Variable { mutable: true },
+      }),
+      None => Err(self.not_found(id, &full_path)),
+    }
+  }
 
-    FullIdentifier::Global(path)
+  fn not_found(
+    &self,
+    id: &Identifier,
+    full_path: &FullIdentifier,
+  ) -> Box<SemanticError> {
+    let module = full_path.module_path();
+    let name = id.name();
+    let mut candidates: Vec<_> = self
+      .items
+      .keys()
+      .filter(|path| path.module_path() == module)
+      .map(|path| path.name())
+      .collect();
+    if id.is_singular() {
+      candidates.extend(self.aliases.keys().cloned());
+      candidates.extend(self.scopes.iter().flat_map(|scope| {
+        scope.locals.iter().map(|local| local.name.value.clone())
+      }));
+    }
+    let threshold = (name.len() / 3).max(1);
+    let suggestion = candidates
+      .into_iter()
+      .map(|candidate| (distance(&name, &candidate), candidate))
+      .filter(|(distance, _)| *distance <= threshold)
+      .min();
+
+    let mut err = SemanticError::new(
+      &self.current_path,
+      &format!("Cannot find `{name}` in module `{module}`"),
+      id.span(()),
+    )
+    .with_label("not found");
+    if let Some((_, suggestion)) = suggestion {
+      err = err.with_note(&format!("A similar name exists: `{suggestion}`"));
+    } else if id.is_singular() {
+      // The item may only need to be brought in scope
+      let mut elsewhere: Vec<_> = self
+        .items
+        .keys()
+        .filter(|path| path.name() == name)
+        .collect();
+      elsewhere.sort();
+      if let Some(FullIdentifier::Global(path)) = elsewhere.first() {
+        err = err.with_note(&format!(
+          "An item with this name exists elsewhere: `use ::{};`",
+          path.join("::")
+        ));
+      }
+    }
+    Box::new(err)
+  }
+}
+
+/// Levenshtein distance between two names
+fn distance(a: &str, b: &str) -> usize {
+  let b: Vec<_> = b.chars().collect();
+  let mut previous: Vec<_> = (0..=b.len()).collect();
+  for (i, ca) in a.chars().enumerate() {
+    let mut current = vec![i + 1];
+    for (j, cb) in b.iter().enumerate() {
+      let substitution = previous[j] + usize::from(ca != *cb);
+      current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+    }
+    previous = current;
+  }
+  previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    grammar::identifier::FullIdentifier,
+    report::message::MessageType,
+    testing::{analyze, error},
+  };
+  use std::path::Path;
+
+  /// Slots of the locals of `function` in `source`, in declaration order
+  fn slots(source: &str, function: &str) -> Vec<String> {
+    let (program, _) = analyze(source).unwrap();
+    let id = FullIdentifier::Global(vec![function.to_string()]);
+    let function = program.function(&id).unwrap();
+    function
+      .locals
+      .iter()
+      .map(|local| local.name.clone())
+      .collect()
+  }
+
+  #[test]
+  fn shadowing_variables_get_their_own_slots() {
+    let source = "fn main() {
+  let x = 1;
+  {
+    let x = 2;
+  }
+  let x = 3;
+}
+fn other(x: int) {
+  let x = x;
+}";
+    assert_eq!(slots(source, "main"), ["x", "x-1", "x-2"]);
+    // The parameter holds the slot `x`
+    assert_eq!(slots(source, "other"), ["x-1"]);
+  }
+
+  #[test]
+  fn block_scope_ends_with_the_block() {
+    let source = "fn main() -> int {
+  let x = 1;
+  {
+    let x = true;
+  }
+  return x;
+}";
+    assert!(analyze(source).is_ok());
+  }
+
+  #[test]
+  fn block_variables_are_not_visible_after_it() {
+    let err = error("fn main() { { let y = 1; } let z = y; }");
+    assert_eq!(err.message, "Cannot find `y` in module `main`");
+    assert_eq!(err.focus.column, 35);
+  }
+
+  #[test]
+  fn unknown_name() {
+    let err = error("fn main() { let a = b; }");
+    assert_eq!(err.message, "Cannot find `b` in module `main`");
+    assert_eq!(err.focus.value.as_deref(), Some("not found"));
+  }
+
+  #[test]
+  fn use_of_unknown_item() {
+    let err = error("use foo::bar;");
+    assert_eq!(err.message, "Cannot find `bar` in module `foo`");
+  }
+
+  #[test]
+  fn assignment_to_immutable_local() {
+    let source = "fn main() {
+  let x = 1;
+  x = 2;
+}";
+    let err = error(source);
+    assert_eq!(err.message, "Cannot assign to immutable variable `x`");
+    assert_eq!(err.focus.line, 3);
+    let declaration = &err.helpers[0];
+    assert_eq!(declaration.value, "declared here");
+    assert_eq!((declaration.line, declaration.column), (2, 6));
+    assert!(err.notes.is_empty());
+
+    let suggestion = err.suggestion.as_ref().unwrap();
+    assert_eq!(suggestion.message, "Consider making the variable mutable");
+    let insertion = &suggestion.insertion;
+    assert_eq!(insertion.value, "mut ");
+    assert_eq!((insertion.line, insertion.column, insertion.len), (2, 6, 3));
+    assert_eq!(suggestion.apply(source).unwrap(), "  let mut x = 1;");
+    let help = err.get_help(Path::new("lib.lc"), source).unwrap();
+    assert_eq!(help.typ(), MessageType::Help);
+  }
+
+  #[test]
+  fn assignment_to_parameter() {
+    let err = error("fn f(a: int) { a += 1; }");
+    assert_eq!(err.message, "Cannot assign to immutable variable `a`");
+    assert_eq!(err.helpers[0].column, 5);
+    assert_eq!(
+      err.notes,
+      ["Consider assigning to a mutable copy: `let mut a = a;`"]
+    );
+    assert!(err.suggestion.is_none());
+  }
+
+  #[test]
+  fn assignment_to_immutable_global() {
+    let source = "let count = 0;
+fn main() { count = 1; }";
+    let err = error(source);
+    assert_eq!(err.message, "Cannot assign to immutable variable `count`");
+    assert_eq!(err.helpers[0].line, 1);
+    let suggestion = err.suggestion.unwrap();
+    assert_eq!(suggestion.apply(source).unwrap(), "let mut count = 0;");
+  }
+
+  #[test]
+  fn assignment_to_field_of_immutable_local() {
+    let err = error(
+      "struct P { x: int }
+fn main() { let p = P { x: 1 }; p.x = 2; }",
+    );
+    assert_eq!(err.message, "Cannot assign to immutable variable `p`");
+  }
+
+  #[test]
+  fn assignment_to_constant() {
+    let err = error(
+      "const N: int = 1;
+fn main() { N = 2; }",
+    );
+    assert_eq!(err.message, "Cannot assign to constant `N`");
+    assert_eq!(err.helpers[0].line, 1);
+    assert_eq!(
+      err.notes,
+      ["Constants are replaced by their value where they are used"]
+    );
   }
 }
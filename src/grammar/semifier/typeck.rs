@@ -0,0 +1,1139 @@
+use super::{
+  ast::{always_jumps, FnDecl, FnStatement, ResolvedExpression},
+  consteval::cycle_error,
+  error::SemanticError,
+  module::ModulePath,
+  program::Program,
+};
+use crate::{
+  grammar::{
+    builtins::{parse_format, BuiltinFn, BuiltinType, FormatPart},
+    identifier::{CallTarget, FullIdentifier, Name, Type},
+    operators::{AssignOp, BinOp, UnOp},
+    parser::ast::Expression,
+  },
+  report::location::WithLineInfo,
+};
+use std::collections::{HashMap, HashSet};
+
+type ResolvedType = Type<FullIdentifier>;
+
+/// Verifies that every function and global of a program is well typed, and
+/// infers the types of variables declared without annotation
+pub struct TypeChecker<'a> {
+  program: &'a Program,
+  module: ModulePath,
+  globals: HashMap<FullIdentifier, ResolvedType>,
+  // Globals whose type is being inferred, each read by the initializer of
+  // the previous one, to catch initializers that depend on themselves
+  inferring: Vec<FullIdentifier>,
+  locals: HashMap<Name, ResolvedType>,
+  // Fixed-point value of `1.0`, see FloatConfig
+  float_scale: i32,
+  warnings: Vec<SemanticError>,
+}
+
+impl<'a> TypeChecker<'a> {
+  pub fn new(program: &'a Program, float_scale: i32) -> Self {
+    Self {
+      program,
+      module: ModulePath::main(),
+      globals: HashMap::new(),
+      inferring: Vec::new(),
+      locals: HashMap::new(),
+      float_scale,
+      warnings: Vec::new(),
+    }
+  }
+
+  /// Checks the whole program, filling in inferred types. Also returns
+  /// warnings about code that compiles but may not behave as written.
+  pub fn check(
+    mut program: Program,
+    float_scale: i32,
+  ) -> Result<(Program, Vec<SemanticError>), Box<SemanticError>> {
+    let mut globals: Vec<_> =
+      program.globals().map(|(id, _)| id.clone()).collect();
+    let mut functions: Vec<_> =
+      program.functions().map(|(id, _)| id.clone()).collect();
+    let mut structs: Vec<_> =
+      program.structs().map(|(id, _)| id.clone()).collect();
+    globals.sort();
+    functions.sort();
+    structs.sort();
+
+    let mut checker = TypeChecker::new(&program, float_scale);
+    for id in &structs {
+      checker.structure(id)?;
+    }
+    for id in &globals {
+      checker.global(id)?;
+    }
+    let mut locals = Vec::new();
+    for id in &functions {
+      checker.function(program.function(id).unwrap())?;
+      locals.push((id, checker.locals.clone()));
+    }
+    let global_types = checker.globals;
+    let warnings = checker.warnings;
+
+    for (id, typ) in global_types {
+      let global = program.global_mut(&id).unwrap();
+      if global.typ.is_none() {
+        global.typ = Some(global.val.span().map(|_| typ));
+      }
+    }
+    for (id, types) in locals {
+      let function = program.function_mut(id).unwrap();
+      for local in &mut function.locals {
+        local.typ = types.get(&local.name).cloned();
+      }
+    }
+    Ok((program, warnings))
+  }
+
+  /// Checks that a struct does not contain itself, which would make its
+  /// values infinitely large
+  fn structure(&self, id: &FullIdentifier) -> Result<(), Box<SemanticError>> {
+    if !self.contains(id, id, &mut HashSet::new()) {
+      return Ok(());
+    }
+    let decl = self.program.structure(id).unwrap();
+    Err(
+      SemanticError::new(
+        &decl.module,
+        &format!("Struct `{id}` contains itself"),
+        decl.name.clone().map(|_| ()),
+      )
+      .with_label("recursive struct")
+      .with_note(
+        "A struct cannot hold a value of its own type, even indirectly",
+      )
+      .into(),
+    )
+  }
+
+  fn contains(
+    &self,
+    outer: &FullIdentifier,
+    inner: &FullIdentifier,
+    visited: &mut HashSet<FullIdentifier>,
+  ) -> bool {
+    if !visited.insert(inner.clone()) {
+      return false;
+    }
+    let decl = self.program.structure(inner).unwrap();
+    decl.fields.iter().any(|field| match &field.typ {
+      Type::Declared(id) => id == outer || self.contains(outer, id, visited),
+      Type::Array(_) | Type::Builtin(_) => false,
+    })
+  }
+
+  fn global(
+    &mut self,
+    id: &FullIdentifier,
+  ) -> Result<ResolvedType, Box<SemanticError>> {
+    if let Some(typ) = self.globals.get(id) {
+      return Ok(typ.clone());
+    }
+    let program = self.program;
+    let global = program.global(id).unwrap();
+    // An annotation gives the type before the initializer is checked, so
+    // that globals read from each other are left to the constant evaluator
+    if let Some(annotation) = &global.typ {
+      self.globals.insert(id.clone(), annotation.value.clone());
+    }
+    if let Some(start) = self.inferring.iter().position(|step| step == id) {
+      return Err(cycle_error(program, &self.inferring[start..], id));
+    }
+    self.inferring.push(id.clone());
+
+    // The initializer is checked in the context of the global's own module
+    let module = std::mem::replace(&mut self.module, global.module.clone());
+    let locals = std::mem::take(&mut self.locals);
+    let typ = match &global.typ {
+      Some(annotation) => {
+        let typ = self.typed(&global.val, &annotation.value)?;
+        self.expect(&global.val, &typ, annotation)?;
+        typ
+      }
+      None => {
+        let typ = self.expression(&global.val)?;
+        self.storable(&global.val, &typ)?;
+        typ
+      }
+    };
+    self.module = module;
+    self.locals = locals;
+
+    let typ = global.typ.as_ref().map_or(typ, |typ| typ.value.clone());
+    self.inferring.pop();
+    self.globals.insert(id.clone(), typ.clone());
+    Ok(typ)
+  }
+
+  fn function(&mut self, decl: &FnDecl) -> Result<(), Box<SemanticError>> {
+    self.module = decl.module.clone();
+    self.locals = decl
+      .params
+      .iter()
+      .map(|param| (param.name.clone(), param.typ.clone()))
+      .collect();
+
+    for statement in &decl.body {
+      self.statement(decl, statement)?;
+    }
+
+    if !decl.returns_void() && !always_jumps(&decl.body) {
+      let mut err = self
+        .error(
+          &format!("Function `{}` may end without returning", decl.name.value),
+          decl.name.clone().map(|_| ()),
+        )
+        .with_note("Every path through the function must end with `return`");
+      if let Some(span) = &decl.ret_span {
+        err = err
+          .with_helper(span.clone(), &format!("returns `{}`", decl.ret_type));
+      }
+      return Err(err.into());
+    }
+    Ok(())
+  }
+
+  fn statement(
+    &mut self,
+    decl: &FnDecl,
+    statement: &FnStatement,
+  ) -> Result<(), Box<SemanticError>> {
+    match statement {
+      FnStatement::VarDecl { name, typ, val } => {
+        let local_type = match typ {
+          Some(annotation) => {
+            let val_type = self.typed(val, &annotation.value)?;
+            self.expect(val, &val_type, annotation)?;
+            annotation.value.clone()
+          }
+          None => {
+            let val_type = self.expression(val)?;
+            self.storable(val, &val_type)?;
+            val_type
+          }
+        };
+        self.locals.insert(name.value.clone(), local_type);
+      }
+      FnStatement::Assignment { target, op, val } => {
+        let target_type = self.expression(target)?;
+        let target = target.span();
+        let val_type = self.typed(val, &target_type)?;
+        let concatenates =
+          op.value == AssignOp::Add && target_type == Type::string();
+        if op.value != AssignOp::Identity
+          && !target_type.is_numeric()
+          && !concatenates
+        {
+          return Err(
+            self
+              .error(
+                "Compound assignment on a non numeric variable",
+                op.clone().map(|_| ()),
+              )
+              .with_helper(target, &format!("has type `{target_type}`"))
+              .into(),
+          );
+        }
+        if val_type != target_type {
+          return Err(
+            self
+              .mismatch(val, &target_type, &val_type)
+              .with_helper(target, &format!("has type `{target_type}`"))
+              .into(),
+          );
+        }
+      }
+      FnStatement::SideEffect(expr) => {
+        self.expression(expr)?;
+      }
+      FnStatement::If {
+        cond,
+        then,
+        otherwise,
+      } => {
+        self.condition(cond)?;
+        for statement in then.iter().chain(otherwise) {
+          self.statement(decl, statement)?;
+        }
+      }
+      FnStatement::Loop { cond, body, step } => {
+        if let Some(cond) = cond {
+          self.condition(cond)?;
+        }
+        for statement in body.iter().chain(step) {
+          self.statement(decl, statement)?;
+        }
+      }
+      FnStatement::Break | FnStatement::Continue => {}
+      FnStatement::Return(ret) => {
+        let val_type = match &ret.value {
+          Some(expr) => self.typed(expr, &decl.ret_type)?,
+          None => Type::void(),
+        };
+        if val_type != decl.ret_type {
+          let focus = match &ret.value {
+            Some(expr) => expr.span(),
+            None => ret.clone().map(|_| ()),
+          };
+          let mut err =
+            self
+              .error("Mismatched return type", focus)
+              .with_label(&format!(
+                "expected `{}`, found `{val_type}`",
+                decl.ret_type
+              ));
+          err = match &decl.ret_span {
+            Some(span) => {
+              err.with_helper(span.clone(), "expected because of this")
+            }
+            None => err.with_helper(
+              decl.name.clone().map(|_| ()),
+              "function returns nothing",
+            ),
+          };
+          return Err(err.into());
+        }
+      }
+    }
+    Ok(())
+  }
+
+  fn condition(
+    &mut self,
+    cond: &ResolvedExpression,
+  ) -> Result<(), Box<SemanticError>> {
+    let cond_type = self.expression(cond)?;
+    let bool_type = Type::Builtin(BuiltinType::Bool);
+    if cond_type != bool_type {
+      return Err(self.mismatch(cond, &bool_type, &cond_type).into());
+    }
+    Ok(())
+  }
+
+  /// Infers the type of an expression, checking operands along the way
+  pub fn expression(
+    &mut self,
+    expr: &ResolvedExpression,
+  ) -> Result<ResolvedType, Box<SemanticError>> {
+    match expr {
+      Expression::AtomBoolean(_) => Ok(Type::Builtin(BuiltinType::Bool)),
+      Expression::AtomInteger(value) => {
+        self.int_literal(value, false)?;
+        Ok(Type::Builtin(BuiltinType::Int))
+      }
+      Expression::AtomFloat(value) => {
+        self.float_literal(value);
+        Ok(Type::Builtin(BuiltinType::Float))
+      }
+      Expression::AtomString(_) => Ok(Type::Builtin(BuiltinType::String)),
+      Expression::AtomIdentifier(id) => self.identifier(id),
+      Expression::Struct(id, fields) => self.literal(id, fields),
+      Expression::Field(expr, name) => {
+        let typ = self.expression(expr)?;
+        self.field(&typ, name)
+      }
+      Expression::Array(span, elements) => {
+        let Some(first) = elements.first() else {
+          return Err(
+            self
+              .error("Cannot infer the type of an empty array", span.clone())
+              .with_note("Annotate the variable with its type, as in `[int]`")
+              .into(),
+          );
+        };
+        let element = self.expression(first)?;
+        self.storable(first, &element)?;
+        let typ = Type::Array(Box::new(element));
+        self.typed(expr, &typ)
+      }
+      Expression::Index(expr, index) => {
+        let typ = self.expression(expr)?;
+        let element = self.element(expr, &typ)?;
+        let int_type = Type::Builtin(BuiltinType::Int);
+        let index_type = self.expression(index)?;
+        if index_type != int_type {
+          return Err(self.mismatch(index, &int_type, &index_type).into());
+        }
+        Ok(element)
+      }
+      Expression::Call(target, args) => self.call(target, args),
+      Expression::UnOp(op, operand) => {
+        if let (UnOp::Negate, Expression::AtomInteger(value)) =
+          (op.value, operand.as_ref())
+        {
+          self.int_literal(value, true)?;
+          return Ok(Type::Builtin(BuiltinType::Int));
+        }
+        let typ = self.expression(operand)?;
+        let valid = match op.value {
+          UnOp::Not => typ == Type::Builtin(BuiltinType::Bool),
+          UnOp::Negate | UnOp::Identity => typ.is_numeric(),
+          UnOp::BitNot => typ == Type::Builtin(BuiltinType::Int),
+        };
+        if valid {
+          Ok(typ)
+        } else {
+          Err(
+            self
+              .error(
+                &format!("Cannot apply unary operator to `{typ}`"),
+                op.clone().map(|_| ()),
+              )
+              .with_helper(operand.span(), &format!("has type `{typ}`"))
+              .into(),
+          )
+        }
+      }
+      Expression::Cast(operand, typ) => {
+        let found = self.expression(operand)?;
+        let target = Type::Builtin(typ.value);
+        if found.is_numeric() && target.is_numeric() {
+          Ok(target)
+        } else {
+          Err(
+            self
+              .error(
+                &format!("Cannot cast `{found}` to `{target}`"),
+                expr.span(),
+              )
+              .with_label("invalid cast")
+              .with_note("Only `int` and `float` convert to each other")
+              .into(),
+          )
+        }
+      }
+      Expression::BinOp(left, op, right) => {
+        let left_type = self.expression(left)?;
+        let right_type = self.typed(right, &left_type)?;
+        let (valid, result) = match op.value {
+          // Strings concatenate
+          BinOp::Add => (
+            left_type.is_numeric() || left_type == Type::string(),
+            left_type.clone(),
+          ),
+          BinOp::Sub | BinOp::Mul | BinOp::Div | BinOp::Mod => {
+            (left_type.is_numeric(), left_type.clone())
+          }
+          BinOp::Equal | BinOp::NotEqual => {
+            (left_type != Type::void(), Type::Builtin(BuiltinType::Bool))
+          }
+          BinOp::Less
+          | BinOp::LessOrEqual
+          | BinOp::Greater
+          | BinOp::GreaterOrEqual => {
+            (left_type.is_numeric(), Type::Builtin(BuiltinType::Bool))
+          }
+          BinOp::And | BinOp::Or => {
+            let bool_type = Type::Builtin(BuiltinType::Bool);
+            (left_type == bool_type, bool_type)
+          }
+          BinOp::BitAnd
+          | BinOp::BitOr
+          | BinOp::BitXor
+          | BinOp::ShiftLeft
+          | BinOp::ShiftRight => (
+            left_type == Type::Builtin(BuiltinType::Int),
+            left_type.clone(),
+          ),
+        };
+        if valid && left_type == right_type {
+          Ok(result)
+        } else {
+          Err(
+            self
+              .error(
+                &format!(
+                  "Cannot apply binary operator to `{left_type}` and \
+                   `{right_type}`"
+                ),
+                op.clone().map(|_| ()),
+              )
+              .with_helper(left.span(), &format!("has type `{left_type}`"))
+              .with_helper(right.span(), &format!("has type `{right_type}`"))
+              .into(),
+          )
+        }
+      }
+    }
+  }
+
+  /// Infers the type of an expression whose expected type is known, which
+  /// lets empty arrays take their type from the context
+  fn typed(
+    &mut self,
+    expr: &ResolvedExpression,
+    expected: &ResolvedType,
+  ) -> Result<ResolvedType, Box<SemanticError>> {
+    let (Expression::Array(_, elements), Type::Array(element)) =
+      (expr, expected)
+    else {
+      return self.expression(expr);
+    };
+    for val in elements {
+      let found = self.typed(val, element)?;
+      if found != **element {
+        return Err(self.mismatch(val, element, &found).into());
+      }
+    }
+    Ok(expected.clone())
+  }
+
+  /// Type of the elements of an array of type `typ`
+  fn element(
+    &self,
+    expr: &ResolvedExpression,
+    typ: &ResolvedType,
+  ) -> Result<ResolvedType, Box<SemanticError>> {
+    match typ {
+      Type::Array(element) => Ok(*element.clone()),
+      _ => Err(
+        self
+          .error(&format!("Expected an array, found `{typ}`"), expr.span())
+          .with_label("not an array")
+          .into(),
+      ),
+    }
+  }
+
+  /// Checks that an integer literal fits in a scoreboard, once negated if it
+  /// is the operand of `-`
+  fn int_literal(
+    &self,
+    value: &WithLineInfo<isize>,
+    negated: bool,
+  ) -> Result<(), Box<SemanticError>> {
+    let signed = if negated { -value.value } else { value.value };
+    if i32::try_from(signed).is_ok() {
+      return Ok(());
+    }
+    Err(
+      self
+        .error(
+          "Integer literal does not fit in a scoreboard",
+          value.clone().map(|_| ()),
+        )
+        .with_label("out of range")
+        .with_note(&format!("Integers range from {} to {}", i32::MIN, i32::MAX))
+        .into(),
+    )
+  }
+
+  /// Warns about float literals out of the range of the fixed-point
+  /// representation, which are clamped to it
+  fn float_literal(&mut self, value: &WithLineInfo<f64>) {
+    let scale = f64::from(self.float_scale);
+    if (value.value * scale).round().abs() <= f64::from(i32::MAX) {
+      return;
+    }
+    let max = f64::from(i32::MAX) / scale;
+    let warning = self
+      .error(
+        &format!(
+          "Float literal `{:?}` does not fit in a scoreboard",
+          value.value
+        ),
+        value.clone().map(|_| ()),
+      )
+      .with_label(&format!("clamped to {max}"))
+      .with_note(&format!(
+        "With a scale of {}, floats range from -{max} to {max}",
+        self.float_scale
+      ));
+    self.warnings.push(warning);
+  }
+
+  fn identifier(
+    &mut self,
+    id: &WithLineInfo<FullIdentifier>,
+  ) -> Result<ResolvedType, Box<SemanticError>> {
+    match &id.value {
+      FullIdentifier::Local(name) => {
+        self.locals.get(name).cloned().ok_or_else(|| {
+          self
+            .error(
+              "Use of a variable before its declaration",
+              id.clone().map(|_| ()),
+            )
+            .into()
+        })
+      }
+      FullIdentifier::Global(_) if self.program.global(&id.value).is_some() => {
+        self.global(&id.value)
+      }
+      FullIdentifier::Global(_) => Err(
+        self
+          .error(
+            &format!("`{}` is not a variable", id.value),
+            id.clone().map(|_| ()),
+          )
+          .with_label("expected a variable")
+          .into(),
+      ),
+    }
+  }
+
+  fn call(
+    &mut self,
+    target: &WithLineInfo<CallTarget<WithLineInfo<FullIdentifier>>>,
+    args: &[ResolvedExpression],
+  ) -> Result<ResolvedType, Box<SemanticError>> {
+    let id = match &target.value {
+      CallTarget::Builtin(BuiltinFn::PrintLn) => {
+        return self.print(args).map(|_| Type::void())
+      }
+      CallTarget::Builtin(bfn) => return self.builtin(target, *bfn, args),
+      CallTarget::Declared(id) => id,
+    };
+    let program = self.program;
+    let callee = program.function(&id.value).ok_or_else(|| {
+      self
+        .error(
+          &format!("`{}` is not a function", id.value),
+          id.clone().map(|_| ()),
+        )
+        .with_label("expected a function")
+    })?;
+
+    if args.len() != callee.params.len() {
+      let plural = |count| if count == 1 { "" } else { "s" };
+      return Err(
+        self
+          .error(
+            &format!(
+              "Function `{}` takes {} argument{} but {} {} given",
+              id.value,
+              callee.params.len(),
+              plural(callee.params.len()),
+              args.len(),
+              if args.len() == 1 { "was" } else { "were" }
+            ),
+            target.clone().map(|_| ()),
+          )
+          .with_note(&format!("Declared as `{}`", signature(callee)))
+          .into(),
+      );
+    }
+    for (arg, param) in args.iter().zip(&callee.params) {
+      let typ = self.typed(arg, &param.typ)?;
+      if typ != param.typ {
+        return Err(
+          self
+            .mismatch(arg, &param.typ, &typ)
+            .with_note(&format!("Declared as `{}`", signature(callee)))
+            .into(),
+        );
+      }
+    }
+    Ok(callee.ret_type.clone())
+  }
+
+  /// Checks a call to one of the builtins operating on arrays
+  fn builtin(
+    &mut self,
+    target: &WithLineInfo<CallTarget<WithLineInfo<FullIdentifier>>>,
+    bfn: BuiltinFn,
+    args: &[ResolvedExpression],
+  ) -> Result<ResolvedType, Box<SemanticError>> {
+    let expected = bfn.arity().unwrap_or(args.len());
+    if args.len() != expected {
+      return Err(
+        self
+          .error(
+            &format!(
+              "Builtin `{}` takes {expected} argument{} but {} {} given",
+              bfn.name(),
+              if expected == 1 { "" } else { "s" },
+              args.len(),
+              if args.len() == 1 { "was" } else { "were" }
+            ),
+            target.clone().map(|_| ()),
+          )
+          .with_note(&format!("Declared as `{}`", bfn.signature()))
+          .into(),
+      );
+    }
+    let typ = self.expression(&args[0])?;
+    Ok(match bfn {
+      BuiltinFn::Len => {
+        if typ != Type::string() {
+          self.element(&args[0], &typ)?;
+        }
+        Type::Builtin(BuiltinType::Int)
+      }
+      BuiltinFn::Substring => {
+        let int_type = Type::Builtin(BuiltinType::Int);
+        let mut found = typ;
+        for (index, arg) in args.iter().enumerate() {
+          if index > 0 {
+            found = self.expression(arg)?;
+          }
+          let expected = if index == 0 {
+            Type::string()
+          } else {
+            int_type.clone()
+          };
+          if found != expected {
+            return Err(
+              self
+                .mismatch(arg, &expected, &found)
+                .with_note(&format!("Declared as `{}`", bfn.signature()))
+                .into(),
+            );
+          }
+        }
+        Type::string()
+      }
+      BuiltinFn::Pop => self.element(&args[0], &typ)?,
+      BuiltinFn::Push => {
+        let element = self.element(&args[0], &typ)?;
+        let found = self.typed(&args[1], &element)?;
+        if found != element {
+          return Err(
+            self
+              .mismatch(&args[1], &element, &found)
+              .with_helper(args[0].span(), &format!("has type `{typ}`"))
+              .into(),
+          );
+        }
+        Type::void()
+      }
+      BuiltinFn::PrintLn => unreachable!(),
+    })
+  }
+
+  /// Checks a call to `println`, whose first argument is a format string
+  /// unless it is the only one
+  fn print(
+    &mut self,
+    args: &[ResolvedExpression],
+  ) -> Result<(), Box<SemanticError>> {
+    let values = match args.first() {
+      Some(Expression::AtomString(format)) => {
+        let span = format.clone().map(|_| ());
+        let parts = parse_format(&format.value).map_err(|brace| {
+          self
+            .error("Invalid format string", span.clone())
+            .with_label(&format!("unmatched `{brace}`"))
+            .with_note("Write `{{` and `}}` to print braces")
+        })?;
+        let expected = parts
+          .iter()
+          .filter(|part| **part == FormatPart::Placeholder)
+          .count();
+        let found = args.len() - 1;
+        if expected != found {
+          let plural = |count| if count == 1 { "" } else { "s" };
+          return Err(
+            self
+              .error(
+                &format!(
+                  "Format string takes {expected} argument{} but {found} {} \
+                   given",
+                  plural(expected),
+                  if found == 1 { "was" } else { "were" }
+                ),
+                span,
+              )
+              .with_label(&format!(
+                "{expected} placeholder{}",
+                plural(expected)
+              ))
+              .into(),
+          );
+        }
+        &args[1..]
+      }
+      Some(value) if args.len() == 1 => std::slice::from_ref(value),
+      Some(value) => {
+        return Err(
+          self
+            .error("Format string must be a string literal", value.span())
+            .with_label("not a string literal")
+            .with_note("Values are printed with `println(\"{}\", value)`")
+            .into(),
+        )
+      }
+      None => &[],
+    };
+    for value in values {
+      if self.expression(value)? == Type::void() {
+        return Err(
+          self
+            .error("Cannot print a value of type `void`", value.span())
+            .with_label("this expression has no value")
+            .into(),
+        );
+      }
+    }
+    Ok(())
+  }
+
+  /// Checks that an expression has the type of an annotation
+  fn expect(
+    &self,
+    expr: &ResolvedExpression,
+    found: &ResolvedType,
+    annotation: &WithLineInfo<ResolvedType>,
+  ) -> Result<(), Box<SemanticError>> {
+    if *found == annotation.value {
+      Ok(())
+    } else {
+      Err(
+        self
+          .mismatch(expr, &annotation.value, found)
+          .with_helper(annotation.clone().map(|_| ()), "expected due to this")
+          .into(),
+      )
+    }
+  }
+
+  /// Checks that the value of an expression can be stored in a variable
+  fn storable(
+    &self,
+    expr: &ResolvedExpression,
+    found: &ResolvedType,
+  ) -> Result<(), Box<SemanticError>> {
+    if *found == Type::void() {
+      Err(
+        self
+          .error("Cannot store a value of type `void`", expr.span())
+          .with_label("this expression has no value")
+          .into(),
+      )
+    } else {
+      Ok(())
+    }
+  }
+
+  fn literal(
+    &mut self,
+    id: &WithLineInfo<FullIdentifier>,
+    fields: &[(WithLineInfo<Name>, ResolvedExpression)],
+  ) -> Result<ResolvedType, Box<SemanticError>> {
+    let typ = Type::Declared(id.value.clone());
+    let decl = self.program.structure(&id.value).unwrap();
+    for (index, (name, val)) in fields.iter().enumerate() {
+      let previous = fields[..index]
+        .iter()
+        .find(|(previous, _)| previous.value == name.value);
+      if let Some((previous, _)) = previous {
+        return Err(
+          self
+            .error(
+              &format!("Field `{}` is initialized multiple times", name.value),
+              name.clone().map(|_| ()),
+            )
+            .with_label("initialized again here")
+            .with_helper(previous.clone().map(|_| ()), "first initialized here")
+            .into(),
+        );
+      }
+      let expected = self.field(&typ, name)?;
+      let found = self.typed(val, &expected)?;
+      if found != expected {
+        return Err(self.mismatch(val, &expected, &found).into());
+      }
+    }
+
+    let missing: Vec<_> = decl
+      .fields
+      .iter()
+      .filter(|field| fields.iter().all(|(name, _)| name.value != field.name))
+      .map(|field| format!("`{}`", field.name))
+      .collect();
+    if !missing.is_empty() {
+      return Err(
+        self
+          .error(
+            &format!("Missing fields in struct `{typ}`"),
+            id.clone().map(|_| ()),
+          )
+          .with_label(&format!("missing {}", missing.join(", ")))
+          .into(),
+      );
+    }
+    Ok(typ)
+  }
+
+  /// Type of a field of a value of type `typ`
+  fn field(
+    &self,
+    typ: &ResolvedType,
+    name: &WithLineInfo<Name>,
+  ) -> Result<ResolvedType, Box<SemanticError>> {
+    let field = match typ {
+      Type::Declared(id) => self
+        .program
+        .structure(id)
+        .and_then(|decl| decl.field(&name.value)),
+      Type::Array(_) | Type::Builtin(_) => None,
+    };
+    match field {
+      Some(field) => Ok(field.typ.clone()),
+      None => Err(
+        self
+          .error(
+            &format!("No field `{}` on type `{typ}`", name.value),
+            name.clone().map(|_| ()),
+          )
+          .with_label("unknown field")
+          .into(),
+      ),
+    }
+  }
+
+  fn mismatch(
+    &self,
+    expr: &ResolvedExpression,
+    expected: &ResolvedType,
+    found: &ResolvedType,
+  ) -> SemanticError {
+    self
+      .error("Mismatched types", expr.span())
+      .with_label(&format!("expected `{expected}`, found `{found}`"))
+  }
+
+  fn error(&self, message: &str, focus: WithLineInfo<()>) -> SemanticError {
+    SemanticError::new(&self.module, message, focus)
+  }
+}
+
+fn signature(decl: &FnDecl) -> String {
+  let params = decl
+    .params
+    .iter()
+    .map(|param| format!("{}: {}", param.name, param.typ))
+    .collect::<Vec<_>>()
+    .join(", ");
+  if decl.returns_void() {
+    format!("fn {}({params})", decl.name.value)
+  } else {
+    format!("fn {}({params}) -> {}", decl.name.value, decl.ret_type)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{
+    grammar::identifier::FullIdentifier,
+    testing::{analyze, error},
+  };
+
+  fn local_types(source: &str, function: &str) -> Vec<(String, String)> {
+    let (program, _) = analyze(source).unwrap();
+    let id = FullIdentifier::Global(vec![function.to_string()]);
+    program
+      .function(&id)
+      .unwrap()
+      .locals
+      .iter()
+      .map(|local| {
+        let typ = local.typ.as_ref().unwrap();
+        (local.name.clone(), typ.to_string())
+      })
+      .collect()
+  }
+
+  #[test]
+  fn too_many_arguments() {
+    let err = error(
+      "fn f(a: int) -> int { return a; }
+      fn main() { f(1, 2); }",
+    );
+    assert_eq!(
+      err.message,
+      "Function `f` takes 1 argument but 2 were given"
+    );
+    assert_eq!(err.notes, ["Declared as `fn f(a: int) -> int`"]);
+  }
+
+  #[test]
+  fn too_few_builtin_arguments() {
+    let err = error("fn main() { let mut a = [1]; push(a); }");
+    assert_eq!(
+      err.message,
+      "Builtin `push` takes 2 arguments but 1 was given"
+    );
+  }
+
+  #[test]
+  fn argument_of_the_wrong_type() {
+    let err = error(
+      "fn f(a: int) {}
+      fn main() { f(1.5); }",
+    );
+    assert_eq!(err.message, "Mismatched types");
+    assert_eq!(
+      err.focus.value.as_deref(),
+      Some("expected `int`, found `float`")
+    );
+  }
+
+  #[test]
+  fn annotation_mismatch() {
+    let err = error("fn main() { let a: bool = 1; }");
+    assert_eq!(err.message, "Mismatched types");
+    assert_eq!(err.helpers[0].value, "expected due to this");
+  }
+
+  #[test]
+  fn operands_of_different_types() {
+    let err = error("fn main() { let a = 1 + 2.0; }");
+    assert_eq!(
+      err.message,
+      "Cannot apply binary operator to `int` and `float`"
+    );
+  }
+
+  #[test]
+  fn non_bool_condition() {
+    let err = error("fn main() { if 1 {} }");
+    assert_eq!(
+      err.focus.value.as_deref(),
+      Some("expected `bool`, found `int`")
+    );
+  }
+
+  #[test]
+  fn missing_return() {
+    let err = error("fn f(a: bool) -> int { if a { return 1; } }");
+    assert_eq!(err.message, "Function `f` may end without returning");
+  }
+
+  #[test]
+  fn let_without_type_is_inferred() {
+    let types = local_types(
+      "struct P { x: float }
+      fn main() {
+        let a = 1;
+        let b = 2.5 * 2.0;
+        let c = [a, 2];
+        let d = P { x: b };
+        let e = d.x > 1.0;
+      }",
+      "main",
+    );
+    let expected = [
+      ("a", "int"),
+      ("b", "float"),
+      ("c", "[int]"),
+      ("d", "P"),
+      ("e", "bool"),
+    ];
+    let expected: Vec<_> = expected
+      .iter()
+      .map(|(name, typ)| (name.to_string(), typ.to_string()))
+      .collect();
+    assert_eq!(types, expected);
+  }
+
+  #[test]
+  fn untyped_global_is_inferred() {
+    let (program, _) = analyze("let g = \"a\" + \"b\";").unwrap();
+    let id = FullIdentifier::Global(vec![String::from("g")]);
+    let typ = program.global(&id).unwrap().typ.as_ref().unwrap();
+    assert_eq!(typ.value.to_string(), "string");
+  }
+
+  #[test]
+  fn cycle_of_untyped_globals() {
+    let err = error("let a = b + 1;\nlet b = a * 2;");
+    assert_eq!(err.message, "The initializer of `a` depends on itself");
+    assert_eq!(err.notes, ["Cycle: `a` -> `b` -> `a`"]);
+    assert_eq!(err.focus.line, 1);
+  }
+
+  #[test]
+  fn cycle_through_a_typed_global() {
+    let err = error("let a = b;\nlet b: int = c;\nlet c = a;");
+    assert_eq!(err.notes, ["Cycle: `a` -> `b` -> `c` -> `a`"]);
+  }
+
+  #[test]
+  fn empty_array_needs_a_type() {
+    let err = error("fn main() { let a = []; }");
+    assert_eq!(err.message, "Cannot infer the type of an empty array");
+    assert!(analyze("fn main() { let a: [int] = []; }").is_ok());
+  }
+
+  #[test]
+  fn logic_operands_must_be_bool() {
+    let err = error("fn main() { let a = 1 && true; }");
+    assert_eq!(
+      err.message,
+      "Cannot apply binary operator to `int` and `bool`"
+    );
+    assert_eq!(err.helpers[0].value, "has type `int`");
+  }
+
+  #[test]
+  fn bitwise_operands_must_be_int() {
+    let err = error("fn main() { let a = 1.0 << 2; }");
+    assert_eq!(
+      err.message,
+      "Cannot apply binary operator to `float` and `int`"
+    );
+  }
+
+  #[test]
+  fn struct_literal_missing_a_field() {
+    let err =
+      error("struct P { x: int, y: int } fn main() { let p = P { x: 1 }; }");
+    assert_eq!(err.message, "Missing fields in struct `P`");
+    assert_eq!(err.focus.value.as_deref(), Some("missing `y`"));
+  }
+
+  #[test]
+  fn unknown_field() {
+    let err =
+      error("struct P { x: int } fn main() { let p = P { x: 1, z: 2 }; }");
+    assert_eq!(err.message, "No field `z` on type `P`");
+    let err = error("fn main() { let a = 1; let b = a.x; }");
+    assert_eq!(err.message, "No field `x` on type `int`");
+  }
+
+  #[test]
+  fn field_of_the_wrong_type() {
+    let err = error("struct P { x: int } fn main() { let p = P { x: true }; }");
+    assert_eq!(
+      err.focus.value.as_deref(),
+      Some("expected `int`, found `bool`")
+    );
+  }
+
+  #[test]
+  fn strings_are_only_concatenated_with_strings() {
+    let err = error("fn main() { let s = \"a\" + 1; }");
+    assert_eq!(
+      err.message,
+      "Cannot apply binary operator to `string` and `int`"
+    );
+  }
+
+  #[test]
+  fn format_string_arity() {
+    let err = error("fn main() { println(\"{} {}\", 1); }");
+    assert_eq!(
+      err.message,
+      "Format string takes 2 arguments but 1 was given"
+    );
+    assert_eq!(err.focus.value.as_deref(), Some("2 placeholders"));
+  }
+
+  #[test]
+  fn constant_of_the_wrong_type() {
+    let err = error("const N: int = 1.5;");
+    assert_eq!(
+      err.focus.value.as_deref(),
+      Some("expected `int`, found `float`")
+    );
+    assert_eq!(err.helpers[0].value, "expected due to this");
+  }
+}
@@ -0,0 +1,70 @@
+use crate::{grammar::identifier::FullIdentifier, report::message::Message};
+use std::fmt;
+
+/// Resource location of a generated function: `namespace:path/to/name`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FunctionId {
+  pub namespace: String,
+  pub path: Vec<String>,
+}
+
+impl FunctionId {
+  pub fn from_identifier(namespace: &str, id: &FullIdentifier) -> Self {
+    match id {
+      FullIdentifier::Global(path) => Self {
+        namespace: namespace.to_string(),
+        path: path.iter().map(|part| mangle(part)).collect(),
+      },
+      FullIdentifier::Local(name) => Message::compiler_bug(&format!(
+        "Tried to generate a function for local identifier `{name}`"
+      ))
+      .report_and_exit(1),
+    }
+  }
+
+  /// Functions generated by the compiler itself. Their names contain a dash,
+  /// which no mangled identifier can, so they never clash with user code.
+  pub fn internal(namespace: &str, name: &str) -> Self {
+    Self {
+      namespace: namespace.to_string(),
+      path: vec![format!("lc-{name}")],
+    }
+  }
+}
+
+impl FunctionId {
+  /// Function holding a block of code belonging to this function, like the
+  /// branch of an `if`
+  pub fn block(&self, suffix: &str) -> Self {
+    let mut path = self.path.clone();
+    if let Some(name) = path.last_mut() {
+      name.push('-');
+      name.push_str(suffix);
+    }
+    Self {
+      namespace: self.namespace.clone(),
+      path,
+    }
+  }
+}
+
+impl fmt::Display for FunctionId {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}:{}", self.namespace, self.path.join("/"))
+  }
+}
+
+// Resource locations cannot contain uppercase letters, while identifiers
+// cannot contain dots, so `getNum` becomes `get.num` without collisions.
+fn mangle(name: &str) -> String {
+  let mut mangled = String::with_capacity(name.len());
+  for c in name.chars() {
+    if c.is_ascii_uppercase() {
+      mangled.push('.');
+      mangled.push(c.to_ascii_lowercase());
+    } else {
+      mangled.push(c);
+    }
+  }
+  mangled
+}
@@ -0,0 +1,209 @@
+use super::{
+  function::FunctionId,
+  location::DataLocation,
+  register::{Register, OBJECTIVE},
+  value::Value,
+};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+  Equal,
+  NotEqual,
+  Less,
+  LessOrEqual,
+  Greater,
+  GreaterOrEqual,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+  /// Creates the scoreboard objective holding the registers
+  CreateObjective,
+  Set(Register, i32),
+  Move(Register, Register),
+  Swap(Register, Register),
+  Add(Register, Register),
+  Sub(Register, Register),
+  Mul(Register, Register),
+  Div(Register, Register),
+  Mod(Register, Register),
+  /// Adds a constant other than `i32::MIN`, written as its magnitude
+  AddConstant(Register, i32),
+  /// Sets the first register to 1 if the comparison holds, 0 otherwise
+  Compare(Register, Register, Comparison, Register),
+  /// Sets the first register to 1 if the second is 0, 0 otherwise
+  Not(Register, Register),
+  /// Sets the register to 1 if the instruction succeeds, 0 otherwise
+  StoreSuccess(Register, Box<Instruction>),
+  Load(Register, DataLocation),
+  Store(DataLocation, Register),
+  /// Sets the location to an SNBT value
+  DataSet(DataLocation, String),
+  /// Prepends an SNBT value to the list at the location
+  DataPrepend(DataLocation, String),
+  /// Appends an SNBT value to the list at the location
+  DataAppend(DataLocation, String),
+  /// Appends the value at the second location to the list at the first
+  DataAppendFrom(DataLocation, DataLocation),
+  DataCopy(DataLocation, DataLocation),
+  /// Sets the first location to the part of the string at the second one
+  /// from an index, up to another one if any. Indices may be macro arguments.
+  DataSetString(DataLocation, DataLocation, String, Option<String>),
+  DataRemove(DataLocation),
+  /// Runs a function, optionally with a macro context, optionally storing
+  /// its return value in a register
+  Call {
+    function: FunctionId,
+    context: Option<DataLocation>,
+    result: Option<Register>,
+  },
+  /// Returns from the current function with a value
+  Return(Value),
+  /// Returns from the current function with the result of an instruction
+  ReturnRun(Box<Instruction>),
+  /// Runs an instruction only if the register holds the value
+  If(Register, i32, Box<Instruction>),
+  /// Runs an instruction only if the register does not hold the value
+  Unless(Register, i32, Box<Instruction>),
+  /// Shows a JSON text component to every player
+  Tellraw(String),
+  /// Instruction whose `$(name)` placeholders are filled from the macro
+  /// context of the function
+  Macro(Box<Instruction>),
+}
+
+impl Comparison {
+  fn operator(&self) -> &'static str {
+    match self {
+      Comparison::Equal | Comparison::NotEqual => "=",
+      Comparison::Less => "<",
+      Comparison::LessOrEqual => "<=",
+      Comparison::Greater => ">",
+      Comparison::GreaterOrEqual => ">=",
+    }
+  }
+}
+
+impl fmt::Display for Instruction {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let operation = |f: &mut fmt::Formatter, dst, op, src| {
+      write!(
+        f,
+        "scoreboard players operation {dst} {OBJECTIVE} {op} {src} {OBJECTIVE}"
+      )
+    };
+    match self {
+      Instruction::CreateObjective => {
+        write!(f, "scoreboard objectives add {OBJECTIVE} dummy")
+      }
+      Instruction::Set(reg, value) => {
+        write!(f, "scoreboard players set {reg} {OBJECTIVE} {value}")
+      }
+      Instruction::Move(dst, src) => operation(f, dst, "=", src),
+      Instruction::Swap(dst, src) => operation(f, dst, "><", src),
+      Instruction::Add(dst, src) => operation(f, dst, "+=", src),
+      Instruction::Sub(dst, src) => operation(f, dst, "-=", src),
+      Instruction::Mul(dst, src) => operation(f, dst, "*=", src),
+      Instruction::Div(dst, src) => operation(f, dst, "/=", src),
+      Instruction::Mod(dst, src) => operation(f, dst, "%=", src),
+      Instruction::AddConstant(reg, value) if *value < 0 => write!(
+        f,
+        "scoreboard players remove {reg} {OBJECTIVE} {}",
+        value.unsigned_abs()
+      ),
+      Instruction::AddConstant(reg, value) => {
+        write!(f, "scoreboard players add {reg} {OBJECTIVE} {value}")
+      }
+      Instruction::Compare(dst, lhs, cmp, rhs) => write!(
+        f,
+        "execute store success score {dst} {OBJECTIVE} {cond} score {lhs} \
+         {OBJECTIVE} {op} {rhs} {OBJECTIVE}",
+        cond = if *cmp == Comparison::NotEqual {
+          "unless"
+        } else {
+          "if"
+        },
+        op = cmp.operator(),
+      ),
+      Instruction::Not(dst, src) => write!(
+        f,
+        "execute store success score {dst} {OBJECTIVE} if score {src} \
+         {OBJECTIVE} matches 0"
+      ),
+      Instruction::StoreSuccess(reg, instruction) => write!(
+        f,
+        "execute store success score {reg} {OBJECTIVE} run {instruction}"
+      ),
+      Instruction::Load(reg, location) => write!(
+        f,
+        "execute store result score {reg} {OBJECTIVE} run data get {location}"
+      ),
+      Instruction::Store(location, reg) => write!(
+        f,
+        "execute store result {location} int 1 run scoreboard players get \
+         {reg} {OBJECTIVE}"
+      ),
+      Instruction::DataSet(location, value) => {
+        write!(f, "data modify {location} set value {value}")
+      }
+      Instruction::DataPrepend(location, value) => {
+        write!(f, "data modify {location} prepend value {value}")
+      }
+      Instruction::DataAppend(location, value) => {
+        write!(f, "data modify {location} append value {value}")
+      }
+      Instruction::DataAppendFrom(dst, src) => {
+        write!(f, "data modify {dst} append from {src}")
+      }
+      Instruction::DataCopy(dst, src) => {
+        write!(f, "data modify {dst} set from {src}")
+      }
+      Instruction::DataSetString(dst, src, start, end) => {
+        write!(f, "data modify {dst} set string {src} {start}")?;
+        if let Some(end) = end {
+          write!(f, " {end}")?;
+        }
+        Ok(())
+      }
+      Instruction::DataRemove(location) => write!(f, "data remove {location}"),
+      Instruction::Call {
+        function,
+        context,
+        result,
+      } => {
+        if let Some(reg) = result {
+          write!(f, "execute store result score {reg} {OBJECTIVE} run ")?;
+        }
+        write!(f, "function {function}")?;
+        if let Some(context) = context {
+          write!(f, " with {context}")?;
+        }
+        Ok(())
+      }
+      Instruction::Return(Value::Constant(value)) => {
+        write!(f, "return {value}")
+      }
+      Instruction::Return(Value::Register(reg)) => {
+        write!(f, "return run scoreboard players get {reg} {OBJECTIVE}")
+      }
+      Instruction::Return(Value::Data(location)) => {
+        write!(f, "return run data get {location}")
+      }
+      Instruction::ReturnRun(instruction) => {
+        write!(f, "return run {instruction}")
+      }
+      Instruction::If(reg, value, instruction) => write!(
+        f,
+        "execute if score {reg} {OBJECTIVE} matches {value} run {instruction}"
+      ),
+      Instruction::Unless(reg, value, instruction) => write!(
+        f,
+        "execute unless score {reg} {OBJECTIVE} matches {value} run \
+         {instruction}"
+      ),
+      Instruction::Tellraw(component) => write!(f, "tellraw @a {component}"),
+      Instruction::Macro(instruction) => write!(f, "${instruction}"),
+    }
+  }
+}
@@ -0,0 +1,42 @@
+use std::fmt;
+
+/// Closest thing we have to a pointer: a storage name and a path inside it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DataLocation {
+  pub storage: String,
+  pub path: String,
+}
+
+impl DataLocation {
+  pub fn new(storage: &str, path: &str) -> Self {
+    Self {
+      storage: storage.to_string(),
+      path: path.to_string(),
+    }
+  }
+
+  /// Storage shared among all LogiCraft datapacks
+  pub fn global(path: &str) -> Self {
+    Self::new("lc:data", path)
+  }
+
+  /// Storage private to the datapack `datapack`
+  pub fn local(datapack: &str, path: &str) -> Self {
+    Self::new(&format!("lc:data/{datapack}"), path)
+  }
+
+  pub fn child(&self, name: &str) -> Self {
+    Self::new(&self.storage, &format!("{}.{name}", self.path))
+  }
+
+  /// Element of the list at this location. `index` may be a macro argument.
+  pub fn index(&self, index: &str) -> Self {
+    Self::new(&self.storage, &format!("{}[{index}]", self.path))
+  }
+}
+
+impl fmt::Display for DataLocation {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "storage {} {}", self.storage, self.path)
+  }
+}
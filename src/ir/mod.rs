@@ -1,4 +1,7 @@
 // This module is definitely not copy pasted from LLVM
 
+pub mod function;
+pub mod instruction;
+pub mod location;
+pub mod register;
 pub mod value;
-
@@ -0,0 +1,32 @@
+use std::fmt;
+
+/// Name of the scoreboard objective holding all registers
+pub const OBJECTIVE: &str = "registers";
+/// Number of registers in each category
+pub const REGISTER_COUNT: u8 = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Register {
+  /// Temporaries saved by the caller
+  T(u8),
+  /// Temporaries saved by the callee
+  S(u8),
+}
+
+impl Register {
+  #[allow(dead_code)]
+  pub fn all() -> impl Iterator<Item = Register> {
+    (0..REGISTER_COUNT)
+      .map(Register::T)
+      .chain((0..REGISTER_COUNT).map(Register::S))
+  }
+}
+
+impl fmt::Display for Register {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Register::T(index) => write!(f, "T{index}"),
+      Register::S(index) => write!(f, "S{index}"),
+    }
+  }
+}
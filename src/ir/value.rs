@@ -1,3 +1,9 @@
-pub struct Value {
-  
-}
\ No newline at end of file
+use super::{location::DataLocation, register::Register};
+
+/// Where the result of an expression lives
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  Constant(i32),
+  Register(Register),
+  Data(DataLocation),
+}
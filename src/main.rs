@@ -1,3 +1,4 @@
+mod codegen;
 mod control;
 mod format;
 mod grammar;
@@ -5,14 +6,19 @@ mod ir;
 mod output;
 mod pipeline;
 mod report;
+#[cfg(test)]
+mod testing;
 
+use codegen::{function::McFunction, CodeGenerator};
 use control::cli::{getargs, OutputType};
-use control::config::getconfig;
-use format::pack::PackMeta;
+use control::config::{getconfig, Config};
+use format::{pack::PackMeta, tag::FunctionTag, version::Release};
 use output::dir::DirOutputFilesystem;
-use output::OutputFilesystem;
+use output::zip::ZipOutputFilesystem;
+use output::{OutputDirectory, OutputFilesystem, Overwrite};
 use pipeline::Pipeline;
-use report::message::{Message, MessageType};
+use report::message::Message;
+use std::{cell::RefCell, rc::Rc};
 
 fn main() {
   let args = getargs();
@@ -24,8 +30,12 @@ fn main() {
 
   // Lex main.lc
   let src_path = args.source.join("src");
-  let pipeline = Pipeline::new(&src_path);
-  pipeline.run();
+  // The scale is checked to fit when loading the configuration
+  let float_scale = config.float.scale as i32;
+  let pipeline = Pipeline::new(&src_path, args.warn_unused, float_scale);
+  let program = pipeline.run();
+  let generator = CodeGenerator::new(&config.datapack.name, float_scale);
+  let functions = generator.generate(&program);
 
   let output_name = format!(
     "{name}-{version}-{format}{ext}",
@@ -36,31 +46,138 @@ fn main() {
   );
   let destination = args.output.join(&output_name);
 
-  let filesystem = match DirOutputFilesystem::new(&destination, args.force) {
-    Some(fs) => fs,
-    None => Message::new(
-      "Destination already exists. Use -f to overwrite.",
-      MessageType::Error,
-    )
-    .report_and_exit(1),
+  let overwrite = if args.force_any {
+    Overwrite::Anything
+  } else if args.force {
+    Overwrite::Output
+  } else {
+    Overwrite::Nothing
   };
+  let filesystem: Box<dyn OutputFilesystem> = match args.output_type {
+    OutputType::Directory => {
+      Box::new(DirOutputFilesystem::new(&destination, overwrite))
+    }
+    OutputType::Zip => {
+      Box::new(ZipOutputFilesystem::new(&destination, overwrite))
+    }
+  };
+
+  write_datapack(filesystem.as_ref(), &config, &generator, &functions);
+  filesystem.finish();
+}
 
+/// Writes the datapack made of `functions` to any output, on disk or not
+fn write_datapack(
+  filesystem: &dyn OutputFilesystem,
+  config: &Config,
+  generator: &CodeGenerator,
+  functions: &[McFunction],
+) {
+  let target = &config.datapack.minecraft;
   let pack = filesystem.root().borrow_mut().file("pack.mcmeta");
-  let data = filesystem.root().borrow_mut().subdirectory("data");
+  let pack_content = serde_json::to_string_pretty(&PackMeta::new(
+    &config.datapack.description,
+    target,
+  ))
+  .unwrap_or_else(|err| {
+    Message::compiler_bug(&format!(
+      "Error while trying to generate `pack.mcmeta`: {}",
+      err
+    ))
+    .report_and_exit(1)
+  });
+  pack.borrow_mut().write(pack_content.as_bytes());
+
+  for (i, variant) in target.variants().iter().enumerate() {
+    let root = filesystem.root();
+    let root = if i == 0 {
+      root
+    } else {
+      let overlay =
+        root.borrow_mut().subdirectory(&variant.overlay_directory());
+      overlay
+    };
+    write_data(root, &variant.release, config, generator, functions);
+  }
+}
+
+/// Writes the `data` directory of the datapack for `release` to `root`
+fn write_data(
+  root: Rc<RefCell<dyn OutputDirectory>>,
+  release: &Release,
+  config: &Config,
+  generator: &CodeGenerator,
+  functions: &[McFunction],
+) {
+  let data = root.borrow_mut().subdirectory("data");
   let namespace = data.borrow_mut().subdirectory(&config.datapack.name);
-  let function = namespace.borrow_mut().subdirectory("function");
-  let poc = function.borrow_mut().file("poc.mcfunction");
-
-  let pack_content =
-    serde_json::to_string_pretty(&PackMeta::new(&config.datapack.description))
-      .unwrap_or_else(|err| {
-        Message::compiler_bug(&format!(
-          "Error while trying to generate `pack.mcmeta`: {}",
-          err.to_string()
-        ))
-        .report_and_exit(1)
-      });
+  let function_dir = release.function_directory();
+  let function = namespace.borrow_mut().subdirectory(function_dir);
+  let minecraft = data.borrow_mut().subdirectory("minecraft");
+  let tags = minecraft.borrow_mut().subdirectory("tags");
+  let function_tags = tags.borrow_mut().subdirectory(function_dir);
+  let load_tag = function_tags.borrow_mut().file("load.json");
 
-  pack.borrow_mut().write(pack_content.as_bytes());
-  poc.borrow_mut().write("say Hello, World!".as_bytes());
+  let load = FunctionTag::new(&[generator.load_id().to_string()]);
+  let load_content =
+    serde_json::to_string_pretty(&load).unwrap_or_else(|err| {
+      Message::compiler_bug(&format!(
+        "Error while trying to generate `load.json`: {}",
+        err
+      ))
+      .report_and_exit(1)
+    });
+
+  load_tag.borrow_mut().write(load_content.as_bytes());
+  for mcfunction in functions {
+    mcfunction.write(function.clone());
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{getconfig, write_datapack, CodeGenerator, Pipeline};
+  use crate::output::memory::MemoryOutputFilesystem;
+  use std::{env, fs, path::Path};
+
+  /// Compiles the project in `tests/fixtures/{name}` in memory and compares
+  /// the datapack with `tests/golden/{name}`, or rewrites the golden directory
+  /// when `UPDATE_GOLDEN` is set
+  fn snapshot(name: &str) {
+    let tests = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    let source = tests.join("fixtures").join(name);
+    let golden = tests.join("golden").join(name);
+
+    let config = getconfig(source.join("lc.toml"));
+    let float_scale = config.float.scale as i32;
+    let program = Pipeline::new(&source.join("src"), false, float_scale).run();
+    let generator = CodeGenerator::new(&config.datapack.name, float_scale);
+    let functions = generator.generate(&program);
+    let filesystem = MemoryOutputFilesystem::new();
+    write_datapack(&filesystem, &config, &generator, &functions);
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+      let _ = fs::remove_dir_all(&golden);
+      for (path, data) in filesystem.files() {
+        let path = golden.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, data).unwrap();
+      }
+      return;
+    }
+    let differences = filesystem.compare(&golden).unwrap();
+    let differences: Vec<_> =
+      differences.iter().map(ToString::to_string).collect();
+    assert!(
+      differences.is_empty(),
+      "`{name}` differs from its golden directory (set UPDATE_GOLDEN to \
+       accept the changes): {}",
+      differences.join(", ")
+    );
+  }
+
+  #[test]
+  fn counter() {
+    snapshot("counter");
+  }
 }
@@ -0,0 +1,270 @@
+use crate::report::message::Message;
+
+use super::{OutputDirectory, OutputFile, OutputFilesystem};
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
+// Golden directories are only compared by the snapshot tests
+#[cfg(test)]
+use std::{fmt, fs, io, path::Path};
+
+/// Output kept in memory, for tools working on compilation results and for
+/// comparing them against a golden directory without touching the disk
+pub struct MemoryOutputFilesystem {
+  root: Rc<RefCell<MemoryOutputDirectory>>,
+}
+
+pub struct MemoryOutputDirectory {
+  entries: BTreeMap<String, MemoryOutputEntry>,
+}
+pub struct MemoryOutputFile {
+  data: Vec<u8>,
+}
+
+enum MemoryOutputEntry {
+  File(Rc<RefCell<MemoryOutputFile>>),
+  Directory(Rc<RefCell<MemoryOutputDirectory>>),
+}
+
+/// Way a file of the output differs from the golden directory
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Difference {
+  /// In the golden directory but not in the output
+  Missing(String),
+  /// In the output but not in the golden directory
+  Unexpected(String),
+  /// In both, with different contents
+  Changed(String),
+}
+
+impl MemoryOutputFilesystem {
+  pub fn new() -> Self {
+    Self {
+      root: Rc::new(RefCell::new(MemoryOutputDirectory::new())),
+    }
+  }
+
+  /// Every entry of the output, keyed by its path from the root with `/` as
+  /// separator. Directories end with `/` and have no data.
+  pub fn entries(&self) -> BTreeMap<String, Option<Vec<u8>>> {
+    let mut entries = BTreeMap::new();
+    self.root.borrow().collect("", &mut entries);
+    entries
+  }
+
+  /// Every file of the output, keyed like `entries`
+  #[cfg(test)]
+  pub fn files(&self) -> BTreeMap<String, Vec<u8>> {
+    self
+      .entries()
+      .into_iter()
+      .filter_map(|(path, data)| Some((path, data?)))
+      .collect()
+  }
+
+  /// Compares the files of the output with the ones in `golden`. Empty
+  /// directories are not compared.
+  #[cfg(test)]
+  pub fn compare(&self, golden: &Path) -> io::Result<Vec<Difference>> {
+    let mut expected = BTreeMap::new();
+    read_golden(golden, "", &mut expected)?;
+    let mut actual = self.files();
+
+    let mut differences = Vec::new();
+    for (path, data) in expected {
+      match actual.remove(&path) {
+        None => differences.push(Difference::Missing(path)),
+        Some(found) if found != data => {
+          differences.push(Difference::Changed(path))
+        }
+        Some(_) => {}
+      }
+    }
+    differences.extend(actual.into_keys().map(Difference::Unexpected));
+    differences.sort();
+    Ok(differences)
+  }
+}
+impl MemoryOutputDirectory {
+  pub fn new() -> Self {
+    Self {
+      entries: BTreeMap::new(),
+    }
+  }
+
+  fn collect(
+    &self,
+    prefix: &str,
+    entries: &mut BTreeMap<String, Option<Vec<u8>>>,
+  ) {
+    for (name, entry) in &self.entries {
+      match entry {
+        MemoryOutputEntry::File(file) => {
+          let data = file.borrow().data.clone();
+          entries.insert(format!("{prefix}{name}"), Some(data));
+        }
+        MemoryOutputEntry::Directory(dir) => {
+          let path = format!("{prefix}{name}/");
+          entries.insert(path.clone(), None);
+          dir.borrow().collect(&path, entries);
+        }
+      }
+    }
+  }
+}
+impl MemoryOutputFile {
+  pub fn new() -> Self {
+    Self { data: Vec::new() }
+  }
+}
+
+/// Adds the files under `dir` to `files`, keyed by their path from the root
+/// of the golden directory
+#[cfg(test)]
+fn read_golden(
+  dir: &Path,
+  prefix: &str,
+  files: &mut BTreeMap<String, Vec<u8>>,
+) -> io::Result<()> {
+  for entry in fs::read_dir(dir)? {
+    let entry = entry?;
+    let name = entry.file_name().to_string_lossy().to_string();
+    if entry.file_type()?.is_dir() {
+      read_golden(&entry.path(), &format!("{prefix}{name}/"), files)?;
+    } else {
+      files.insert(format!("{prefix}{name}"), fs::read(entry.path())?);
+    }
+  }
+  Ok(())
+}
+
+impl OutputFilesystem for MemoryOutputFilesystem {
+  fn root(&self) -> Rc<RefCell<dyn OutputDirectory>> {
+    self.root.clone()
+  }
+}
+
+impl OutputDirectory for MemoryOutputDirectory {
+  fn subdirectory(&mut self, name: &str) -> Rc<RefCell<dyn OutputDirectory>> {
+    match self.entries.entry(String::from(name)).or_insert_with(|| {
+      MemoryOutputEntry::Directory(Rc::new(RefCell::new(
+        MemoryOutputDirectory::new(),
+      )))
+    }) {
+      MemoryOutputEntry::File(_) => Message::compiler_bug(&format!(
+        "Tried creating a directory when previously a file was created at \
+         the same path: {name:?}"
+      ))
+      .report_and_exit(1),
+      MemoryOutputEntry::Directory(dir) => dir.clone(),
+    }
+  }
+
+  fn file(&mut self, name: &str) -> Rc<RefCell<dyn OutputFile>> {
+    match self.entries.entry(String::from(name)).or_insert_with(|| {
+      MemoryOutputEntry::File(Rc::new(RefCell::new(MemoryOutputFile::new())))
+    }) {
+      MemoryOutputEntry::File(file) => file.clone(),
+      MemoryOutputEntry::Directory(_) => Message::compiler_bug(&format!(
+        "Tried creating a file when previously a directory was created at \
+         the same path: {name:?}"
+      ))
+      .report_and_exit(1),
+    }
+  }
+}
+
+impl OutputFile for MemoryOutputFile {
+  fn write(&mut self, data: &[u8]) {
+    self.data = data.to_vec();
+  }
+}
+
+#[cfg(test)]
+impl fmt::Display for Difference {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Difference::Missing(path) => write!(f, "missing `{path}`"),
+      Difference::Unexpected(path) => write!(f, "unexpected `{path}`"),
+      Difference::Changed(path) => write!(f, "changed `{path}`"),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{Difference, MemoryOutputFilesystem};
+  use crate::output::OutputFilesystem;
+  use std::fs;
+  use tempfile::TempDir;
+
+  /// A fresh golden directory holding `files`
+  fn golden(files: &[(&str, &str)]) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for (path, data) in files {
+      let path = dir.path().join(path);
+      fs::create_dir_all(path.parent().unwrap()).unwrap();
+      fs::write(path, data).unwrap();
+    }
+    dir
+  }
+
+  /// An output holding `pack.mcmeta` and `data/a.txt`
+  fn output() -> MemoryOutputFilesystem {
+    let filesystem = MemoryOutputFilesystem::new();
+    let root = filesystem.root();
+    root
+      .borrow_mut()
+      .file("pack.mcmeta")
+      .borrow_mut()
+      .write(b"{}");
+    let data = root.borrow_mut().subdirectory("data");
+    data.borrow_mut().file("a.txt").borrow_mut().write(b"a");
+    filesystem
+  }
+
+  #[test]
+  fn same_files() {
+    let golden = golden(&[("pack.mcmeta", "{}"), ("data/a.txt", "a")]);
+    assert_eq!(output().compare(golden.path()).unwrap(), []);
+  }
+
+  #[test]
+  fn missing_file() {
+    let golden = golden(&[
+      ("pack.mcmeta", "{}"),
+      ("data/a.txt", "a"),
+      ("data/b.txt", "b"),
+    ]);
+    assert_eq!(
+      output().compare(golden.path()).unwrap(),
+      [Difference::Missing(String::from("data/b.txt"))]
+    );
+  }
+
+  #[test]
+  fn unexpected_file() {
+    let golden = golden(&[("pack.mcmeta", "{}")]);
+    assert_eq!(
+      output().compare(golden.path()).unwrap(),
+      [Difference::Unexpected(String::from("data/a.txt"))]
+    );
+  }
+
+  #[test]
+  fn changed_file() {
+    let golden = golden(&[("pack.mcmeta", "{}"), ("data/a.txt", "b")]);
+    assert_eq!(
+      output().compare(golden.path()).unwrap(),
+      [Difference::Changed(String::from("data/a.txt"))]
+    );
+  }
+
+  #[test]
+  fn empty_directories_are_not_compared() {
+    let golden = golden(&[("pack.mcmeta", "{}"), ("data/a.txt", "a")]);
+    fs::create_dir_all(golden.path().join("empty")).unwrap();
+    let output = output();
+    output.root().borrow_mut().subdirectory("other");
+    assert_eq!(output.compare(golden.path()).unwrap(), []);
+  }
+}
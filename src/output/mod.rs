@@ -1,9 +1,20 @@
 pub mod dir;
+pub mod memory;
+pub mod zip;
 
-use std::{cell::RefCell, rc::Rc};
+use crate::report::message::{Message, MessageType};
+use ::zip::ZipArchive;
+use std::{
+  cell::RefCell,
+  fs, io,
+  path::{Path, PathBuf},
+  rc::Rc,
+};
 
 pub trait OutputFilesystem {
   fn root(&self) -> Rc<RefCell<dyn OutputDirectory>>;
+  /// Called once every file is written
+  fn finish(&self) {}
 }
 
 pub trait OutputDirectory {
@@ -14,3 +25,172 @@ pub trait OutputDirectory {
 pub trait OutputFile {
   fn write(&mut self, data: &[u8]);
 }
+
+/// What an output may replace at its destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overwrite {
+  Nothing,
+  /// Only a previous datapack, directory or zip, with a `pack.mcmeta`
+  Output,
+  Anything,
+}
+
+/// Exits unless `destination` is free or may be replaced
+fn check_destination(destination: &Path, overwrite: Overwrite) {
+  recover(destination);
+  if !destination.exists() && !destination.is_symlink() {
+    return;
+  }
+  match overwrite {
+    Overwrite::Nothing => Message::new(
+      "Destination already exists. Use -f to overwrite.",
+      MessageType::Error,
+    )
+    .report_and_exit(1),
+    Overwrite::Output if !is_output(destination) => Message::new(
+      &format!(
+        "Destination `{}` does not look like a datapack. Use -F to \
+         overwrite it anyway.",
+        destination.to_string_lossy()
+      ),
+      MessageType::Error,
+    )
+    .report_and_exit(1),
+    Overwrite::Output | Overwrite::Anything => {}
+  }
+}
+
+/// Whether `path` is a datapack directory or zip archive
+fn is_output(path: &Path) -> bool {
+  if path.is_dir() {
+    return path.join("pack.mcmeta").is_file();
+  }
+  fs::File::open(path)
+    .ok()
+    .and_then(|file| ZipArchive::new(file).ok())
+    .is_some_and(|mut archive| archive.by_name("pack.mcmeta").is_ok())
+}
+
+/// Cleans up after a replacement of `destination` that was interrupted
+/// between its two renames, putting the previous output back if the new one
+/// did not take its place
+fn recover(destination: &Path) {
+  let previous = temporary_path(destination, "old");
+  if !previous.exists() && !previous.is_symlink() {
+    return;
+  }
+  if destination.exists() || destination.is_symlink() {
+    remove(&previous);
+  } else if let Err(err) = fs::rename(&previous, destination) {
+    Message::output_error(err, destination).report_and_exit(1);
+  }
+}
+
+/// Sibling of `destination` the output is built at before replacing it
+fn temporary_path(destination: &Path, suffix: &str) -> PathBuf {
+  let name = destination
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+  destination.with_file_name(format!(".{name}.{suffix}"))
+}
+
+/// Removes whatever is at `path`, if anything
+fn try_remove(path: &Path) -> io::Result<()> {
+  if path.is_dir() && !path.is_symlink() {
+    fs::remove_dir_all(path)
+  } else if path.exists() || path.is_symlink() {
+    fs::remove_file(path)
+  } else {
+    Ok(())
+  }
+}
+
+fn remove(path: &Path) {
+  try_remove(path)
+    .unwrap_or_else(|err| Message::remove_error(err, path).report_and_exit(1));
+}
+
+/// Builds the output at a temporary sibling of `destination` with `build`,
+/// then moves it to `destination`. The temporary output is removed whenever
+/// it cannot take the place of the destination.
+fn build_and_replace(
+  destination: &Path,
+  build: impl FnOnce(&Path) -> io::Result<()>,
+) {
+  let building = temporary_path(destination, "tmp");
+  remove(&building);
+  if let Err(err) = build(&building) {
+    let _ = try_remove(&building);
+    Message::output_error(err, &building).report_and_exit(1);
+  }
+  replace(&building, destination);
+}
+
+/// Moves the output built at `built` to `destination`. A file replaces
+/// another in a single rename, and anything else is exchanged with it where
+/// the system allows. Otherwise the previous output is first moved aside to
+/// `.name.old`, and put back if the new one cannot take its place. If the
+/// process is killed between the two renames, `recover` finishes the job on
+/// the next run.
+fn replace(built: &Path, destination: &Path) {
+  let fail = |err: io::Error, path: &Path| -> ! {
+    let _ = try_remove(built);
+    Message::output_error(err, path).report_and_exit(1)
+  };
+  let in_place = destination.exists() || destination.is_symlink();
+  if !in_place || (built.is_file() && destination.is_file()) {
+    if let Err(err) = fs::rename(built, destination) {
+      fail(err, destination);
+    }
+    return;
+  }
+  // The previous output ends up at `built`
+  if exchange(built, destination).is_ok() {
+    remove(built);
+    return;
+  }
+
+  let previous = temporary_path(destination, "old");
+  remove(&previous);
+  if let Err(err) = fs::rename(destination, &previous) {
+    fail(err, &previous);
+  }
+  if let Err(err) = fs::rename(built, destination) {
+    let _ = fs::rename(&previous, destination);
+    fail(err, destination);
+  }
+  remove(&previous);
+}
+
+/// Swaps the entries at `a` and `b` in a single step
+#[cfg(target_os = "linux")]
+fn exchange(a: &Path, b: &Path) -> io::Result<()> {
+  use std::{ffi::CString, os::unix::ffi::OsStrExt};
+  let c_path = |path: &Path| {
+    CString::new(path.as_os_str().as_bytes())
+      .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+  };
+  let (a, b) = (c_path(a)?, c_path(b)?);
+  // SAFETY: both paths are NUL-terminated and outlive the call
+  let result = unsafe {
+    libc::renameat2(
+      libc::AT_FDCWD,
+      a.as_ptr(),
+      libc::AT_FDCWD,
+      b.as_ptr(),
+      libc::RENAME_EXCHANGE,
+    )
+  };
+  if result == 0 {
+    Ok(())
+  } else {
+    Err(io::Error::last_os_error())
+  }
+}
+
+/// Swaps the entries at `a` and `b` in a single step
+#[cfg(not(target_os = "linux"))]
+fn exchange(_: &Path, _: &Path) -> io::Result<()> {
+  Err(io::ErrorKind::Unsupported.into())
+}
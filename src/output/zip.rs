@@ -0,0 +1,110 @@
+use super::{
+  build_and_replace, check_destination, memory::MemoryOutputFilesystem,
+  OutputDirectory, OutputFilesystem, Overwrite,
+};
+use ::zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipWriter};
+use std::{
+  cell::RefCell,
+  fs,
+  io::{self, BufWriter, Write},
+  path::{Path, PathBuf},
+  rc::Rc,
+};
+
+/// Output collected in memory and written as a zip archive by `finish`, next
+/// to the destination before taking its place.
+/// Entries are sorted and carry a fixed timestamp, so compiling the same
+/// sources twice gives the same archive.
+pub struct ZipOutputFilesystem {
+  destination: PathBuf,
+  memory: MemoryOutputFilesystem,
+}
+
+impl ZipOutputFilesystem {
+  pub fn new(destination: &Path, overwrite: Overwrite) -> Self {
+    check_destination(destination, overwrite);
+
+    Self {
+      destination: destination.to_path_buf(),
+      memory: MemoryOutputFilesystem::new(),
+    }
+  }
+
+  fn write_archive(&self, path: &Path) -> io::Result<()> {
+    let file = fs::File::create(path)?;
+    let mut writer = ZipWriter::new(BufWriter::new(file));
+    let options = SimpleFileOptions::default()
+      .compression_method(CompressionMethod::Deflated)
+      .last_modified_time(DateTime::default())
+      .unix_permissions(0o644);
+    for (path, data) in self.memory.entries() {
+      match data {
+        Some(data) => {
+          writer.start_file(path, options)?;
+          writer.write_all(&data)?;
+        }
+        None => writer.add_directory(path, options.unix_permissions(0o755))?,
+      }
+    }
+    writer.finish()?.flush()
+  }
+}
+
+impl OutputFilesystem for ZipOutputFilesystem {
+  fn root(&self) -> Rc<RefCell<dyn OutputDirectory>> {
+    self.memory.root()
+  }
+
+  fn finish(&self) {
+    build_and_replace(&self.destination, |path| self.write_archive(path));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::ZipOutputFilesystem;
+  use crate::output::{OutputFilesystem, Overwrite};
+  use ::zip::ZipArchive;
+  use std::{
+    fs,
+    io::{Cursor, Read},
+  };
+
+  /// Writes an archive holding `pack.mcmeta` and `data/b.txt`, creating them
+  /// in reverse order, and returns its bytes
+  fn write() -> Vec<u8> {
+    let dir = tempfile::tempdir().unwrap();
+    let archive = dir.path().join("pack.zip");
+    let filesystem = ZipOutputFilesystem::new(&archive, Overwrite::Nothing);
+    let root = filesystem.root();
+    let data = root.borrow_mut().subdirectory("data");
+    data.borrow_mut().file("b.txt").borrow_mut().write(b"b");
+    root
+      .borrow_mut()
+      .file("pack.mcmeta")
+      .borrow_mut()
+      .write(b"{}");
+    filesystem.write_archive(&archive).unwrap();
+    fs::read(&archive).unwrap()
+  }
+
+  #[test]
+  fn entries_are_sorted_by_path() {
+    let bytes = write();
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+    let order: Vec<_> = (0..archive.len())
+      .map(|i| archive.by_index(i).unwrap().name().to_string())
+      .collect();
+    assert_eq!(order, ["data/", "data/b.txt", "pack.mcmeta"]);
+
+    let mut file = archive.by_name("data/b.txt").unwrap();
+    let mut content = String::new();
+    file.read_to_string(&mut content).unwrap();
+    assert_eq!(content, "b");
+  }
+
+  #[test]
+  fn archives_are_reproducible() {
+    assert_eq!(write(), write());
+  }
+}
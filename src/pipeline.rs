@@ -2,14 +2,14 @@ use crate::{
   grammar::{
     lexer::Lexer,
     parser::{ast::Node, Parser},
-    semifier::{module::ModulePath, Semifier},
+    semifier::{module::ModulePath, program::Program, Semifier},
   },
   report::message::{Message, MessageType},
 };
 use std::{
   collections::{HashMap, HashSet},
   fs,
-  path::PathBuf,
+  path::{Path, PathBuf},
 };
 
 #[derive(Debug, Clone)]
@@ -17,6 +17,8 @@ struct ModuleLoader;
 
 pub struct Pipeline {
   root: PathBuf,
+  warn_unused: bool,
+  float_scale: i32,
 }
 
 #[derive(Debug, Clone)]
@@ -27,8 +29,12 @@ pub struct Tree {
 }
 
 impl Pipeline {
-  pub fn new(root: &PathBuf) -> Self {
-    Pipeline { root: root.clone() }
+  pub fn new(root: &Path, warn_unused: bool, float_scale: i32) -> Self {
+    Pipeline {
+      root: root.to_path_buf(),
+      warn_unused,
+      float_scale,
+    }
   }
 
   fn load(&self) -> HashMap<ModulePath, Tree> {
@@ -37,19 +43,18 @@ impl Pipeline {
     loader.load(&self.root, ModulePath::main())
   }
 
-  pub fn run(&self) {
+  pub fn run(&self) -> Program {
     let prog = self.load();
-    let semifier = Semifier;
-    semifier.semify(prog);
+    let semifier = Semifier {
+      warn_unused: self.warn_unused,
+      float_scale: self.float_scale,
+    };
+    semifier.semify(prog)
   }
 }
 
 impl ModuleLoader {
-  fn load(
-    &self,
-    root: &PathBuf,
-    module: ModulePath,
-  ) -> HashMap<ModulePath, Tree> {
+  fn load(&self, root: &Path, module: ModulePath) -> HashMap<ModulePath, Tree> {
     let mut schedule = HashSet::from([module]);
     let mut loaded = HashMap::new();
 
@@ -60,7 +65,7 @@ impl ModuleLoader {
       if loaded.contains_key(&next) {
         continue;
       }
-      let all_paths = next.paths(root.clone());
+      let all_paths = next.paths(root.to_path_buf());
       let valid_paths: Vec<_> = all_paths
         .into_iter()
         .filter(|path| match fs::metadata(path) {
@@ -70,7 +75,7 @@ impl ModuleLoader {
         .collect();
       if valid_paths.is_empty() {
         Message::new(
-          &format!("Could not find module `{}`", next.to_string()),
+          &format!("Could not find module `{}`", next),
           MessageType::Error,
         )
         .report_and_exit(1)
@@ -78,18 +83,14 @@ impl ModuleLoader {
       if valid_paths.len() > 1 {
         let paths = valid_paths
           .iter()
-          .map(|path| format!("`{}`", path.to_string_lossy().to_string()))
+          .map(|path| format!("`{}`", path.to_string_lossy()))
           .collect::<Vec<_>>()
           .join(", ");
         Message::new(
-          &format!("Ambiguous module `{}`", next.to_string()),
+          &format!("Ambiguous module `{}`", next),
           MessageType::Error,
         )
-        .with_note(&format!(
-          "Module `{}` could be any of {}",
-          next.to_string(),
-          paths
-        ))
+        .with_note(&format!("Module `{}` could be any of {}", next, paths))
         .report_and_exit(1)
       }
       let path = valid_paths[0].clone();
@@ -109,12 +110,9 @@ impl ModuleLoader {
       };
 
       for node in &nodes {
-        match node {
-          Node::ModDecl(name) => {
-            let path = next.join(name.value.clone());
-            schedule.insert(path);
-          }
-          _ => {}
+        if let Node::ModDecl(name) = node {
+          let path = next.join(name.value.clone());
+          schedule.insert(path);
         }
       }
       loaded.insert(
@@ -21,13 +21,13 @@ impl LineInfoFn {
     let line = self
       .newlines
       .iter()
-      .position(|&i| i > start)
+      .position(|&i| i >= start)
       .unwrap_or(self.newlines.len())
       + 1;
     let column = self
       .newlines
       .get(line.wrapping_sub(2))
-      .map(|&i| start - i)
+      .map(|&i| start - i - 1)
       .unwrap_or(start);
     (line, column, len)
   }
@@ -160,6 +160,8 @@ impl HighlightContext {
           if !remaining_heads.is_empty() {
             let typ = cache.next_pipe(pos, &remaining_heads);
             line_result.push_str(&format!("{margin}").color(typ.color()))
+          } else {
+            line_result.push(' ');
           }
         } else {
           let (c, color) = match spot {
@@ -215,7 +217,7 @@ impl HighlightSet {
 
   fn is_free(&self, start: usize, end: usize, typ: HighlightType) -> bool {
     for i in start..end {
-      if self.highlights.get(&i).map_or(false, |t| *t != typ) {
+      if self.highlights.get(&i).is_some_and(|t| *t != typ) {
         return false;
       }
     }
@@ -263,7 +265,7 @@ impl DetailSet {
   }
 
   fn is_free(&self, start: usize, len: usize) -> bool {
-    let start = if start < 1 { 0 } else { start - 1 };
+    let start = start.saturating_sub(1);
     let end = start + len + 1;
     for i in start..end {
       if self.get(i) != DetailSpot::Free {
@@ -276,11 +278,7 @@ impl DetailSet {
 
 impl DetailSpot {
   fn looks_empty(&self) -> bool {
-    match self {
-      DetailSpot::Free => true,
-      DetailSpot::Padding => true,
-      _ => false,
-    }
+    matches!(self, DetailSpot::Free | DetailSpot::Padding)
   }
 }
 
@@ -295,8 +293,7 @@ impl FinalizeCache {
     column: usize,
     possible_values: &HashSet<HighlightType>,
   ) -> HighlightType {
-    let displayed_values =
-      self.displayed_pipes.entry(column).or_insert(HashSet::new());
+    let displayed_values = self.displayed_pipes.entry(column).or_default();
     let mut possibles_nexts = possible_values
       .iter()
       .filter(|v| !displayed_values.contains(v));
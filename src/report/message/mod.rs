@@ -3,7 +3,11 @@ pub mod line;
 
 use colored::{Color, Colorize};
 use line::HighlightedLine;
-use std::{io, path::PathBuf, process};
+use std::{
+  io,
+  path::{Path, PathBuf},
+  process,
+};
 
 #[derive(Debug, Clone)]
 pub struct Message {
@@ -25,6 +29,7 @@ pub enum MessageType {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ExitBehavior {
   AlwaysExit(i32),
+  #[allow(dead_code)]
   ExitIfEntailed(i32),
   OnlyReport,
 }
@@ -49,21 +54,23 @@ impl Message {
     Self::new(message, MessageType::Bug)
       .with_note("This is a compiler bug. Please report it.")
   }
+  // Nothing is left unimplemented right now
+  #[allow(dead_code)]
   pub fn not_implemented(message: &str) -> Self {
     Self::new(message, MessageType::Bug)
       .with_note("Using feature not yet implemented.")
   }
 
-  pub fn input_error(err: io::Error, path: &PathBuf) -> Self {
-    Self::new(&format!("{}", err.to_string()), MessageType::Error)
+  pub fn input_error(err: io::Error, path: &Path) -> Self {
+    Self::new(&format!("{}", err), MessageType::Error)
       .with_note(&format!("While reading `{}`", path.to_string_lossy()))
   }
-  pub fn remove_error(err: io::Error, path: &PathBuf) -> Self {
-    Self::new(&format!("{}", err.to_string()), MessageType::Error)
+  pub fn remove_error(err: io::Error, path: &Path) -> Self {
+    Self::new(&format!("{}", err), MessageType::Error)
       .with_note(&format!("While removing `{}`", path.to_string_lossy()))
   }
-  pub fn output_error(err: io::Error, path: &PathBuf) -> Self {
-    Self::new(&format!("{}", err.to_string()), MessageType::Error)
+  pub fn output_error(err: io::Error, path: &Path) -> Self {
+    Self::new(&format!("{}", err), MessageType::Error)
       .with_note(&format!("While writing `{}`", path.to_string_lossy()))
   }
 
@@ -82,6 +89,11 @@ impl Message {
     self
   }
 
+  #[cfg(test)]
+  pub fn typ(&self) -> MessageType {
+    self.typ
+  }
+
   pub fn report(&self, exit_behavior: ExitBehavior) -> bool {
     println!(
       "{}: {}",
@@ -103,9 +115,8 @@ impl Message {
     }
 
     match exit_behavior {
-      ExitBehavior::AlwaysExit(code) | ExitBehavior::ExitIfEntailed(code)
-        if self.typ.entails_exit() =>
-      {
+      ExitBehavior::AlwaysExit(code) => process::exit(code),
+      ExitBehavior::ExitIfEntailed(code) if self.typ.entails_exit() => {
         process::exit(code)
       }
       _ => self.typ.entails_exit(),
@@ -137,10 +148,7 @@ impl MessageType {
     }
   }
   pub fn entails_exit(&self) -> bool {
-    match self {
-      MessageType::Error => true,
-      _ => false,
-    }
+    matches!(self, MessageType::Error)
   }
 }
 
@@ -149,6 +157,8 @@ impl MessageMeta {
     match self {
       MessageMeta::FileLocation(path, line, col) => {
         let path = path.to_string_lossy().to_string();
+        // Columns are counted from 0 internally
+        let col = col + 1;
         format!(
           "   {arrow} {path}:{line}:{col}",
           arrow = "-->".blue().bold()
@@ -0,0 +1,372 @@
+//! Helpers compiling a single module given as a string, for unit tests
+
+use crate::{
+  codegen::CodeGenerator,
+  grammar::{
+    lexer::Lexer,
+    parser::Parser,
+    semifier::{
+      error::SemanticError, module::ModulePath, program::Program, Semifier,
+    },
+  },
+  pipeline::Tree,
+};
+use std::{
+  collections::{BTreeMap, HashMap},
+  path::PathBuf,
+};
+
+/// Scale of floats in tests, the default one
+pub const FLOAT_SCALE: i32 = 1000;
+
+/// Namespace of the functions compiled by `compile`
+pub const NAMESPACE: &str = "test";
+
+fn modules(source: &str) -> HashMap<ModulePath, Tree> {
+  let tokens = Lexer
+    .lex(source)
+    .unwrap_or_else(|err| panic!("lexing failed: {err:?}"));
+  let nodes = Parser
+    .parse(&tokens)
+    .unwrap_or_else(|err| panic!("parsing failed: {err:?}"));
+  let tree = Tree {
+    nodes,
+    path: PathBuf::from("lib.lc"),
+    source: source.to_string(),
+  };
+  HashMap::from([(ModulePath::main(), tree)])
+}
+
+/// Resolves, checks and folds `source`, returning the program and warnings
+pub fn analyze(
+  source: &str,
+) -> Result<(Program, Vec<SemanticError>), Box<SemanticError>> {
+  let semifier = Semifier {
+    warn_unused: false,
+    float_scale: FLOAT_SCALE,
+  };
+  semifier.analyze(&modules(source))
+}
+
+/// The error `source` is rejected with
+pub fn error(source: &str) -> SemanticError {
+  match analyze(source) {
+    Ok(_) => panic!("expected an error compiling:\n{source}"),
+    Err(err) => *err,
+  }
+}
+
+/// Commands of every function generated for `source`, by function id
+pub fn compile(source: &str) -> BTreeMap<String, String> {
+  let (mut program, _) = analyze(source)
+    .unwrap_or_else(|err| panic!("compilation failed: {}", err.message));
+  program.shake();
+  CodeGenerator::new(NAMESPACE, FLOAT_SCALE)
+    .generate(&program)
+    .into_iter()
+    .map(|function| {
+      let commands: String = function
+        .instructions
+        .iter()
+        .map(|instruction| format!("{instruction}\n"))
+        .collect();
+      (function.id.to_string(), commands)
+    })
+    .collect()
+}
+
+/// Commands of the function generated for `name` in `source`
+pub fn commands(source: &str, name: &str) -> String {
+  let functions = compile(source);
+  functions
+    .get(&format!("{NAMESPACE}:{name}"))
+    .unwrap_or_else(|| panic!("no function `{name}` in {functions:#?}"))
+    .clone()
+}
+
+/// Loads the datapack compiled from `source`, then runs the function
+/// generated for `name` with `args` in its parameter registers, the way
+/// Minecraft runs the commands, and returns its result. Only scores and
+/// stored numbers are modelled, so the program may not use strings or lists.
+pub fn run(source: &str, name: &str, args: &[i32]) -> i32 {
+  let mut machine = Machine {
+    functions: compile(source),
+    scores: HashMap::new(),
+    storage: HashMap::new(),
+    presaved: Vec::new(),
+    depth: 0,
+  };
+  machine.call(&format!("{NAMESPACE}:lc-load"));
+  for (i, &arg) in args.iter().enumerate() {
+    machine.scores.insert(format!("T{i}"), arg);
+  }
+  machine
+    .call(&format!("{NAMESPACE}:{name}"))
+    .unwrap_or_else(|| panic!("`{name}` returned nothing"))
+}
+
+/// Largest number of nested function calls `run` allows
+const MAX_DEPTH: usize = 1000;
+
+struct Machine {
+  functions: BTreeMap<String, String>,
+  scores: HashMap<String, i32>,
+  /// Stored numbers, by `storage_key`
+  storage: HashMap<String, i32>,
+  /// Number of register sets saved in each stack frame, innermost last
+  presaved: Vec<usize>,
+  depth: usize,
+}
+
+/// What running a command gives
+enum Outcome {
+  Value(i32),
+  Fail,
+  /// A `return`, ending the function
+  Return(i32),
+}
+
+/// Where an `execute store` puts the result of its command
+enum Store {
+  Score(String),
+  Storage(String),
+}
+
+impl Machine {
+  /// Runs the function `id`, returning what it returns if anything
+  fn call(&mut self, id: &str) -> Option<i32> {
+    let commands = self
+      .functions
+      .get(id)
+      .unwrap_or_else(|| panic!("no function `{id}`"))
+      .clone();
+    self.depth += 1;
+    assert!(self.depth < MAX_DEPTH, "too many nested calls");
+    let mut result = None;
+    for command in commands.lines() {
+      if let Outcome::Return(value) = self.execute(command) {
+        result = Some(value);
+        break;
+      }
+    }
+    self.depth -= 1;
+    result
+  }
+
+  fn execute(&mut self, command: &str) -> Outcome {
+    let words: Vec<_> = command.split(' ').collect();
+    match words[..] {
+      ["return", "run", ..] => match self.execute(&words[2..].join(" ")) {
+        Outcome::Value(value) | Outcome::Return(value) => {
+          Outcome::Return(value)
+        }
+        Outcome::Fail => Outcome::Return(0),
+      },
+      ["return", value] => Outcome::Return(value.parse().unwrap()),
+      ["function", id] => match self.call(id) {
+        Some(value) => Outcome::Value(value),
+        None => Outcome::Fail,
+      },
+      ["execute", ..] => self.execute_subcommands(&words[1..]),
+      ["scoreboard", "objectives", "add", ..] | ["tellraw", ..] => {
+        Outcome::Value(0)
+      }
+      ["scoreboard", "players", "get", reg, "registers"] => {
+        Outcome::Value(self.score(reg))
+      }
+      ["scoreboard", "players", action @ ("set" | "add" | "remove"), reg, "registers", value] =>
+      {
+        let value: i32 = value.parse().unwrap();
+        let result = match action {
+          "set" => value,
+          "add" => self.score(reg).wrapping_add(value),
+          _ => self.score(reg).wrapping_sub(value),
+        };
+        self.scores.insert(reg.to_string(), result);
+        Outcome::Value(result)
+      }
+      ["scoreboard", "players", "operation", dst, "registers", op, src, "registers"] =>
+      {
+        let (a, b) = (self.score(dst), self.score(src));
+        let result = match op {
+          "=" => b,
+          "+=" => a.wrapping_add(b),
+          "-=" => a.wrapping_sub(b),
+          "*=" => a.wrapping_mul(b),
+          // Division by zero leaves the score as is
+          "/=" if b == 0 => a,
+          "%=" if b == 0 => a,
+          "/=" => floor_div(a, b),
+          "%=" => a.wrapping_sub(floor_div(a, b).wrapping_mul(b)),
+          "<" => a.min(b),
+          ">" => a.max(b),
+          "><" => {
+            self.scores.insert(src.to_string(), a);
+            b
+          }
+          _ => panic!("unsupported command `{command}`"),
+        };
+        self.scores.insert(dst.to_string(), result);
+        Outcome::Value(result)
+      }
+      ["data", "modify", "storage", "lc:data", "frames", "prepend", ..] => {
+        self.presaved.push(0);
+        Outcome::Value(0)
+      }
+      ["data", "remove", "storage", "lc:data", "frames[0]"] => {
+        self.presaved.pop();
+        Outcome::Value(0)
+      }
+      ["data", "modify", "storage", "lc:data", "frames[0].presaved", "prepend", "value", "{}"] =>
+      {
+        *self.presaved.last_mut().unwrap() += 1;
+        Outcome::Value(0)
+      }
+      ["data", "remove", "storage", "lc:data", "frames[0].presaved[0]"] => {
+        *self.presaved.last_mut().unwrap() -= 1;
+        Outcome::Value(0)
+      }
+      ["data", "modify", "storage", storage, path, "set", "value", value] => {
+        let key = self.storage_key(storage, path);
+        self.storage.retain(|stored, _| !is_within(stored, &key));
+        // Empty lists reset the stack, other values are numbers
+        if value != "[]" {
+          let value = value
+            .parse()
+            .unwrap_or_else(|_| panic!("unsupported command `{command}`"));
+          self.storage.insert(key, value);
+        }
+        Outcome::Value(0)
+      }
+      ["data", "modify", "storage", dst_storage, dst, "set", "from", "storage", src_storage, src] =>
+      {
+        let dst = self.storage_key(dst_storage, dst);
+        let src = self.storage_key(src_storage, src);
+        self.storage.retain(|key, _| !is_within(key, &dst));
+        let copied: Vec<_> = self
+          .storage
+          .iter()
+          .filter_map(|(key, &value)| {
+            let rest = key.strip_prefix(&src)?;
+            is_within(key, &src).then(|| (format!("{dst}{rest}"), value))
+          })
+          .collect();
+        self.storage.extend(copied);
+        Outcome::Value(0)
+      }
+      ["data", "get", "storage", storage, path] => {
+        let key = self.storage_key(storage, path);
+        let value = self.storage.get(&key);
+        Outcome::Value(*value.unwrap_or_else(|| panic!("nothing at `{key}`")))
+      }
+      _ => panic!("unsupported command `{command}`"),
+    }
+  }
+
+  /// Runs the subcommands of an `execute`
+  fn execute_subcommands(&mut self, mut words: &[&str]) -> Outcome {
+    let mut stores = Vec::new();
+    let mut outcome = Outcome::Value(1);
+    loop {
+      match words {
+        [] => break,
+        ["run", command @ ..] => {
+          outcome = self.execute(&command.join(" "));
+          break;
+        }
+        [condition @ ("if" | "unless"), "score", a, "registers", "matches", range, rest @ ..] =>
+        {
+          let value = self.score(a);
+          let matches = match range.split_once("..") {
+            None => value == range.parse::<i32>().unwrap(),
+            Some((min, max)) => {
+              (min.is_empty() || value >= min.parse().unwrap())
+                && (max.is_empty() || value <= max.parse().unwrap())
+            }
+          };
+          if matches != (*condition == "if") {
+            outcome = Outcome::Fail;
+            break;
+          }
+          words = rest;
+        }
+        [condition @ ("if" | "unless"), "score", a, "registers", op, b, "registers", rest @ ..] =>
+        {
+          let (a, b) = (self.score(a), self.score(b));
+          let holds = match *op {
+            "<" => a < b,
+            "<=" => a <= b,
+            "=" => a == b,
+            ">=" => a >= b,
+            ">" => a > b,
+            _ => panic!("unsupported comparison `{op}`"),
+          };
+          if holds != (*condition == "if") {
+            outcome = Outcome::Fail;
+            break;
+          }
+          words = rest;
+        }
+        ["store", kind @ ("result" | "success"), "score", reg, "registers", rest @ ..] =>
+        {
+          stores.push((*kind, Store::Score(reg.to_string())));
+          words = rest;
+        }
+        ["store", kind @ ("result" | "success"), "storage", storage, path, "int", "1", rest @ ..] =>
+        {
+          stores.push((*kind, Store::Storage(self.storage_key(storage, path))));
+          words = rest;
+        }
+        _ => panic!("unsupported subcommands `{}`", words.join(" ")),
+      }
+    }
+
+    if let Outcome::Return(_) = outcome {
+      return outcome;
+    }
+    for (kind, target) in stores {
+      let value = match (&outcome, kind) {
+        (Outcome::Value(value), "result") => *value,
+        (Outcome::Value(_), _) => 1,
+        _ => 0,
+      };
+      match target {
+        Store::Score(reg) => self.scores.insert(reg, value),
+        Store::Storage(key) => self.storage.insert(key, value),
+      };
+    }
+    outcome
+  }
+
+  fn score(&self, reg: &str) -> i32 {
+    self.scores.get(reg).copied().unwrap_or(0)
+  }
+
+  /// Key of a path into a storage, telling apart the stack frames and the
+  /// register sets saved in them
+  fn storage_key(&self, storage: &str, path: &str) -> String {
+    let frame = self.presaved.len();
+    let saved = self.presaved.last().copied().unwrap_or(0);
+    let path = path
+      .replace("frames[0]", &format!("frames#{frame}"))
+      .replace("presaved[0]", &format!("presaved#{saved}"));
+    format!("{storage} {path}")
+  }
+}
+
+/// Whether the storage `key` is `path` or inside it
+fn is_within(key: &str, path: &str) -> bool {
+  key
+    .strip_prefix(path)
+    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Quotient rounded down, like scoreboard division
+fn floor_div(a: i32, b: i32) -> i32 {
+  let quotient = a.wrapping_div(b);
+  if a.wrapping_rem(b) != 0 && (a < 0) != (b < 0) {
+    quotient - 1
+  } else {
+    quotient
+  }
+}
@@ -0,0 +1,25 @@
+data modify storage lc:data frames prepend value {locals:{},presaved:[],fname:"counter:dot",source:"counter"}
+scoreboard players set T0 registers 2
+execute store result storage lc:data frames[0].temps.t0.x int 1 run scoreboard players get T0 registers
+scoreboard players set T0 registers 3
+execute store result storage lc:data frames[0].temps.t0.y int 1 run scoreboard players get T0 registers
+data modify storage lc:data frames[0].locals.p set from storage lc:data frames[0].temps.t0
+data modify storage lc:data frames[0].temps.t1 set value []
+scoreboard players set T0 registers 1
+data modify storage lc:data frames[0].temps.t1 append value 0
+execute store result storage lc:data frames[0].temps.t1[-1] int 1 run scoreboard players get T0 registers
+scoreboard players set T0 registers 2
+data modify storage lc:data frames[0].temps.t1 append value 0
+execute store result storage lc:data frames[0].temps.t1[-1] int 1 run scoreboard players get T0 registers
+data modify storage lc:data frames[0].locals.values set from storage lc:data frames[0].temps.t1
+execute store result score T0 registers run data get storage lc:data frames[0].locals.p.x
+execute store result score T1 registers run data get storage lc:data frames[0].locals.p.y
+scoreboard players operation T0 registers *= T1 registers
+data modify storage lc:data frames[0].locals.values append value 0
+execute store result storage lc:data frames[0].locals.values[-1] int 1 run scoreboard players get T0 registers
+execute store result score T0 registers run data get storage lc:data frames[0].locals.values[-1]
+data remove storage lc:data frames[0].locals.values[-1]
+execute store result score T1 registers run data get storage lc:data frames[0].locals.values[1]
+scoreboard players operation T0 registers += T1 registers
+data remove storage lc:data frames[0]
+return run scoreboard players get T0 registers
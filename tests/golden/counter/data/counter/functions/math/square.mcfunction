@@ -0,0 +1,8 @@
+data modify storage lc:data frames prepend value {locals:{},presaved:[],fname:"counter:math/square",source:"counter"}
+execute store result storage lc:data frames[0].postsaved.S0 int 1 run scoreboard players get S0 registers
+scoreboard players operation S0 registers = T0 registers
+scoreboard players operation T0 registers = S0 registers
+scoreboard players operation T0 registers *= S0 registers
+execute store result score S0 registers run data get storage lc:data frames[0].postsaved.S0
+data remove storage lc:data frames[0]
+return run scoreboard players get T0 registers
@@ -0,0 +1,2 @@
+tellraw @a ["",{"nbt":"globals.count","storage":"lc:data/counter"}]
+return 0
@@ -0,0 +1,4 @@
+scoreboard objectives add registers dummy
+data modify storage lc:data frames set value []
+data modify storage lc:data params set value []
+data modify storage lc:data/counter globals.count set value 0
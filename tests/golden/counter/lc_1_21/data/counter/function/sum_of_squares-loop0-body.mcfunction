@@ -0,0 +1,4 @@
+scoreboard players operation T0 registers = S2 registers
+execute store result score T0 registers run function counter:math/square
+scoreboard players operation S1 registers += T0 registers
+return 0
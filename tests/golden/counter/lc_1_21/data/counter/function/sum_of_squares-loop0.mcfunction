@@ -0,0 +1,7 @@
+scoreboard players operation T0 registers = S2 registers
+execute store success score T0 registers if score T0 registers < S3 registers
+execute unless score T0 registers matches 1 run return 0
+function counter:sum_of_squares-loop0-body
+scoreboard players set T0 registers 1
+scoreboard players operation S2 registers += T0 registers
+return run function counter:sum_of_squares-loop0
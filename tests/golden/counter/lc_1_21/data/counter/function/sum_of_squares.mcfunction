@@ -0,0 +1,20 @@
+data modify storage lc:data frames prepend value {locals:{},presaved:[],fname:"counter:sum_of_squares",source:"counter"}
+execute store result storage lc:data frames[0].postsaved.S0 int 1 run scoreboard players get S0 registers
+execute store result storage lc:data frames[0].postsaved.S1 int 1 run scoreboard players get S1 registers
+execute store result storage lc:data frames[0].postsaved.S2 int 1 run scoreboard players get S2 registers
+execute store result storage lc:data frames[0].postsaved.S3 int 1 run scoreboard players get S3 registers
+scoreboard players operation S0 registers = T0 registers
+scoreboard players set T0 registers 0
+scoreboard players operation S1 registers = T0 registers
+scoreboard players set T0 registers 0
+scoreboard players operation S2 registers = T0 registers
+scoreboard players operation T0 registers = S0 registers
+scoreboard players operation S3 registers = T0 registers
+function counter:sum_of_squares-loop0
+scoreboard players operation T0 registers = S1 registers
+execute store result score S0 registers run data get storage lc:data frames[0].postsaved.S0
+execute store result score S1 registers run data get storage lc:data frames[0].postsaved.S1
+execute store result score S2 registers run data get storage lc:data frames[0].postsaved.S2
+execute store result score S3 registers run data get storage lc:data frames[0].postsaved.S3
+data remove storage lc:data frames[0]
+return run scoreboard players get T0 registers
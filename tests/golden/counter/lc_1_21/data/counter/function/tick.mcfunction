@@ -0,0 +1,12 @@
+data modify storage lc:data frames prepend value {locals:{},presaved:[],fname:"counter:tick",source:"counter"}
+scoreboard players set T0 registers 1
+execute store result score T1 registers run data get storage lc:data/counter globals.count
+scoreboard players operation T1 registers += T0 registers
+execute store result storage lc:data/counter globals.count int 1 run scoreboard players get T1 registers
+execute store result score T0 registers run data get storage lc:data/counter globals.count
+scoreboard players set T1 registers 20
+scoreboard players operation T0 registers %= T1 registers
+scoreboard players set T1 registers 0
+execute store success score T0 registers if score T0 registers = T1 registers
+execute if score T0 registers matches 1 run function counter:tick-if0-then
+data remove storage lc:data frames[0]
@@ -0,0 +1,139 @@
+//! Runs the compiler over an existing datapack, checking it is only replaced
+//! by a complete one
+
+use std::{
+  fs,
+  path::{Path, PathBuf},
+  process::{Command, Output},
+};
+use tempfile::TempDir;
+
+const CONFIG: &str = "[datapack]
+name = \"pack\"
+version = \"1.0\"
+description = \"\"
+format = 1
+";
+
+/// A project in a fresh temporary directory, with an output directory holding
+/// a previous datapack directory
+struct Project(TempDir);
+
+impl Project {
+  fn new(source: &str) -> Self {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::create_dir_all(root.join("src")).unwrap();
+    fs::write(root.join("lc.toml"), CONFIG).unwrap();
+    fs::write(root.join("src").join("lib.lc"), source).unwrap();
+
+    let previous = root.join("out").join("pack-1.0-1");
+    fs::create_dir_all(&previous).unwrap();
+    fs::write(previous.join("pack.mcmeta"), "previous").unwrap();
+    Self(dir)
+  }
+
+  fn out(&self) -> PathBuf {
+    self.0.path().join("out")
+  }
+
+  fn compile(&self, output_type: &str) -> Output {
+    self.compile_with(&["-t", output_type, "-f"])
+  }
+
+  fn compile_with(&self, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_logicraft"))
+      .arg("-s")
+      .arg(self.0.path())
+      .arg("-o")
+      .arg(self.out())
+      .args(args)
+      .output()
+      .unwrap()
+  }
+
+  /// Moves the previous datapack where an interrupted replacement leaves it
+  fn interrupt(&self) {
+    let out = self.out();
+    fs::rename(out.join("pack-1.0-1"), out.join(".pack-1.0-1.old")).unwrap();
+  }
+
+  /// Names of the entries of the output directory
+  fn outputs(&self) -> Vec<String> {
+    let mut names: Vec<_> = fs::read_dir(self.out())
+      .unwrap()
+      .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+      .collect();
+    names.sort();
+    names
+  }
+
+  fn read(&self, path: impl AsRef<Path>) -> Vec<u8> {
+    fs::read(self.out().join(path)).unwrap()
+  }
+}
+
+#[test]
+fn failed_compile_leaves_datapack_untouched() {
+  let project = Project::new(
+    "#[export]
+fn main() {
+  let x: int = true;
+}
+",
+  );
+  for output_type in ["directory", "zip"] {
+    let output = project.compile(output_type);
+    assert!(!output.status.success(), "{output:?}");
+    assert_eq!(project.outputs(), ["pack-1.0-1"]);
+    assert_eq!(project.read("pack-1.0-1/pack.mcmeta"), b"previous");
+  }
+}
+
+#[test]
+fn datapack_is_replaced_without_leftovers() {
+  let project = Project::new(
+    "#[export]
+fn main() {
+  let x = 1;
+}
+",
+  );
+  // The second zip replaces the first one
+  for output_type in ["directory", "zip", "zip"] {
+    let output = project.compile(output_type);
+    assert!(output.status.success(), "{output:?}");
+  }
+  assert_eq!(project.outputs(), ["pack-1.0-1", "pack-1.0-1.zip"]);
+  assert_ne!(project.read("pack-1.0-1/pack.mcmeta"), b"previous");
+}
+
+#[test]
+fn interrupted_replacement_is_undone() {
+  let project = Project::new(
+    "#[export]
+fn main() {}
+",
+  );
+  project.interrupt();
+  // Without -f, the previous datapack is back in the way
+  let output = project.compile_with(&[]);
+  assert!(!output.status.success(), "{output:?}");
+  assert_eq!(project.outputs(), ["pack-1.0-1"]);
+  assert_eq!(project.read("pack-1.0-1/pack.mcmeta"), b"previous");
+}
+
+#[test]
+fn stale_previous_datapack_is_removed() {
+  let project = Project::new(
+    "#[export]
+fn main() {}
+",
+  );
+  project.interrupt();
+  assert!(project.compile("directory").status.success());
+  fs::create_dir(project.out().join(".pack-1.0-1.old")).unwrap();
+  let output = project.compile("directory");
+  assert!(output.status.success(), "{output:?}");
+  assert_eq!(project.outputs(), ["pack-1.0-1"]);
+}